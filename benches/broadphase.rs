@@ -0,0 +1,60 @@
+#![feature(test)]
+
+extern crate test;
+extern crate rand;
+extern crate cgmath;
+extern crate roids;
+
+use test::Bencher;
+use rand::Rng;
+use cgmath::vec2;
+
+use roids::broadphase::{ self, Grid, Proxy };
+use roids::entity::seeded;
+use roids::world::World;
+
+fn field(n: usize) -> Vec<Proxy> {
+    let mut rng = seeded(1);
+    (0..n).map(|_| {
+        let p = vec2(rng.gen_range(-350.0, 350.0), rng.gen_range(-350.0, 350.0));
+        Some((p, rng.gen_range(2.0, 20.0)))
+    }).collect()
+}
+
+/* narrowphase stand-in, so both sides pay for the pairs they produce */
+fn contacts(field: &[Proxy], pairs: Vec<(usize, usize)>) -> usize {
+    pairs.into_iter().filter(|&(i, j)| {
+        let (a, b) = (field[i].unwrap(), field[j].unwrap());
+        World::default().distance(a.0, b.0) < a.1 + b.1
+    }).count()
+}
+
+fn grid() -> Grid {
+    Grid::new(&World::default())
+}
+
+#[bench]
+fn brute_force_1000(b: &mut Bencher) {
+    let field = field(1000);
+    b.iter(|| contacts(&field, broadphase::brute_force(&field)));
+}
+
+#[bench]
+fn grid_1000(b: &mut Bencher) {
+    let field = field(1000);
+    let mut grid = grid();
+    b.iter(|| contacts(&field, grid.pairs(&field)));
+}
+
+#[bench]
+fn brute_force_4000(b: &mut Bencher) {
+    let field = field(4000);
+    b.iter(|| contacts(&field, broadphase::brute_force(&field)));
+}
+
+#[bench]
+fn grid_4000(b: &mut Bencher) {
+    let field = field(4000);
+    let mut grid = grid();
+    b.iter(|| contacts(&field, grid.pairs(&field)));
+}
@@ -0,0 +1,442 @@
+//! Screens around the game itself: title, pause, game over, entering
+//! a name for the high score table and changing the controls.
+
+use std::rc::Rc;
+
+use director::Wave;
+use game::{ Game, Mode };
+use input::{ Key, Input, Snapshot, KEYS };
+use render::{ text, Render };
+use ship::ShipMeta;
+use world::World;
+
+/* entries kept in the high score table, and letters in a name */
+const TABLE_SIZE: usize = 5;
+const NAME_LEN: usize = 3;
+const LETTERS: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+const COLOR: [f32; 4] = [ 0.9, 0.9, 0.9, 1.0 ];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Screen {
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+    HighScore,
+    Controls,
+}
+
+/* name of the first key bound to an action, for on screen hints */
+fn label(input: &Input, key: Key) -> String {
+    input.keymap().bindings_of(key).first().map_or(String::from("?"), |b| b.name().to_uppercase())
+}
+
+/// Runs games one after another, deciding what is on screen.
+pub struct App {
+    screen: Screen,
+    game: Game,
+    games: u32,
+
+    seed: u32,
+    world: World,
+    ship_meta: Rc<ShipMeta>,
+    waves: Vec<Wave>,
+    turret: bool,
+    players: usize,
+    mode: Mode,
+
+    scores: Vec<(String, u32)>,
+    /* letters of the name being entered, and which one is selected */
+    name: [usize; NAME_LEN],
+    cursor: usize,
+
+    /* player and action picked on the controls screen, and whether
+     * the next key pressed gets bound to it */
+    editing: usize,
+    selected: usize,
+    capturing: bool,
+    /* playing back a replay, which can't follow the controls screen */
+    replaying: bool,
+}
+
+impl App {
+    pub fn new(seed: u32, world: World, ship_meta: Rc<ShipMeta>, waves: Vec<Wave>) -> App {
+        let game = Game::new(seed, world, ship_meta.clone(), waves.clone());
+        App {
+            screen: Screen::Title,
+            game: game,
+            games: 0,
+
+            seed: seed,
+            world: world,
+            ship_meta: ship_meta,
+            waves: waves,
+            turret: false,
+            players: 1,
+            mode: Mode::Coop,
+
+            scores: Vec::new(),
+            name: [0; NAME_LEN],
+            cursor: 0,
+
+            editing: 0,
+            selected: 0,
+            capturing: false,
+            replaying: false,
+        }
+    }
+
+    pub fn screen(&self) -> Screen {
+        self.screen
+    }
+
+    pub fn game(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    /// Best scores first.
+    pub fn scores(&self) -> &[(String, u32)] {
+        &self.scores
+    }
+
+    /// Aim with the mouse in this game and the ones after it.
+    pub fn set_turret(&mut self, on: bool) {
+        self.turret = on;
+        self.game.set_turret(on);
+    }
+
+    /// Follow a replay from now on. Rebinding keys reads the keyboard
+    /// directly, which replays don't hold, so the controls screen is
+    /// left out of recordings and can't be opened while replaying.
+    pub fn set_replaying(&mut self, on: bool) {
+        self.replaying = on;
+    }
+
+    /// Play the next games with `players` sharing the screen, getting
+    /// along as `mode` says.
+    pub fn set_players(&mut self, players: usize, mode: Mode) {
+        self.players = players;
+        self.mode = mode;
+        self.game = Game::with_players(self.seed, self.world, self.ship_meta.clone(), self.waves.clone(), players, mode);
+        self.game.set_turret(self.turret);
+    }
+
+    /// Throw the old game away and start over.
+    pub fn restart(&mut self) {
+        self.games += 1;
+        let seed = self.seed.wrapping_add(self.games);
+        self.game = Game::with_players(seed, self.world, self.ship_meta.clone(), self.waves.clone(),
+                                       self.players, self.mode);
+        self.game.set_turret(self.turret);
+        self.screen = Screen::Playing;
+    }
+
+    fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.scores.len() < TABLE_SIZE || self.scores.iter().any(|s| score > s.1))
+    }
+
+    fn enter_name(&mut self) {
+        let name: String = self.name.iter().map(|&i| LETTERS[i] as char).collect();
+        let score = self.game.best_score();
+        let at = self.scores.iter().position(|s| score > s.1).unwrap_or(self.scores.len());
+        self.scores.insert(at, (name, score));
+        self.scores.truncate(TABLE_SIZE);
+    }
+
+    /// Advance one tick with an input for each player, false once the
+    /// first player asks to quit. Menus follow the first player.
+    pub fn think(&mut self, dt: f32, inputs: &mut [Input]) -> bool {
+        let snapshots: Vec<Snapshot> = inputs.iter_mut().map(|i| i.tick(dt).clone()).collect();
+        let hit = |key| snapshots[0].just_pressed(key);
+
+        match self.screen {
+            Screen::Title => {
+                if hit(Key::Quit) {
+                    return false;
+                }
+                if hit(Key::Start) {
+                    self.restart();
+                } else if hit(Key::Pause) && !self.replaying {
+                    self.editing = 0;
+                    self.selected = 0;
+                    self.screen = Screen::Controls;
+                }
+            },
+            Screen::Playing => {
+                if hit(Key::Quit) {
+                    self.screen = Screen::Title;
+                } else if hit(Key::Pause) {
+                    self.screen = Screen::Paused;
+                } else {
+                    self.game.think(dt, &snapshots);
+                    if self.game.over() {
+                        self.screen = Screen::GameOver;
+                    }
+                }
+            },
+            Screen::Paused => {
+                if hit(Key::Quit) {
+                    self.screen = Screen::Title;
+                } else if hit(Key::Pause) || hit(Key::Start) {
+                    self.screen = Screen::Playing;
+                }
+            },
+            Screen::GameOver => {
+                /* let the wreckage settle in the background */
+                self.game.think(dt, &[]);
+                if hit(Key::Start) || hit(Key::Quit) {
+                    if self.qualifies(self.game.best_score()) {
+                        self.name = [0; NAME_LEN];
+                        self.cursor = 0;
+                        self.screen = Screen::HighScore;
+                    } else {
+                        self.screen = Screen::Title;
+                    }
+                }
+            },
+            Screen::HighScore => {
+                let n = LETTERS.len();
+                let letter = &mut self.name[self.cursor];
+                if hit(Key::Forward) {
+                    *letter = (*letter + 1) % n;
+                }
+                if hit(Key::Reverse) {
+                    *letter = (*letter + n - 1) % n;
+                }
+                if hit(Key::Right) {
+                    self.cursor = (self.cursor + 1).min(NAME_LEN - 1);
+                }
+                if hit(Key::Left) {
+                    self.cursor = self.cursor.saturating_sub(1);
+                }
+                if hit(Key::Start) || hit(Key::Fire) {
+                    self.enter_name();
+                    self.screen = Screen::Title;
+                }
+            },
+            Screen::Controls => {
+                let n = inputs.len();
+                let input = &mut inputs[self.editing.min(n - 1)];
+                if self.capturing {
+                    /* a key the menus can't give up is ignored, and the
+                     * next one is tried */
+                    if let Some(binding) = input.take_binding() {
+                        if input.keymap_mut().bind(KEYS[self.selected], binding) {
+                            self.capturing = false;
+                        }
+                    }
+                } else if hit(Key::Quit) {
+                    self.screen = Screen::Title;
+                } else if hit(Key::Start) {
+                    /* forget the key that got us here */
+                    input.take_binding();
+                    self.capturing = true;
+                } else if hit(Key::Pause) {
+                    input.keymap_mut().unbind_oldest(KEYS[self.selected]);
+                } else if hit(Key::Left) {
+                    self.editing = (self.editing + n - 1) % n;
+                } else if hit(Key::Right) {
+                    self.editing = (self.editing + 1) % n;
+                } else if hit(Key::Forward) {
+                    self.selected = (self.selected + KEYS.len() - 1) % KEYS.len();
+                } else if hit(Key::Reverse) {
+                    self.selected = (self.selected + 1) % KEYS.len();
+                }
+            },
+        }
+
+        true
+    }
+
+    pub fn draw(&mut self, renderer: &mut Render, inputs: &[Input], alpha: f32) {
+        /* hints show the keys of the first player, who runs the menus */
+        let input = &inputs[0];
+        match self.screen {
+            Screen::Title => {
+                text::draw_centered(renderer, "ROIDS", 0.0, 60.0, 60.0, COLOR);
+                text::draw_centered(renderer, &format!("PRESS {}", label(input, Key::Start)), 0.0, 0.0, 20.0, COLOR);
+                text::draw_centered(renderer, &format!("{} FOR CONTROLS", label(input, Key::Pause)), 0.0, -30.0, 12.0, COLOR);
+                for (i, &(ref name, score)) in self.scores.iter().enumerate() {
+                    let line = format!("{} {:>7}", name, score);
+                    text::draw_centered(renderer, &line, 0.0, -80.0 - 30.0 * i as f32, 16.0, COLOR);
+                }
+            },
+            Screen::Playing => self.game.draw(renderer, alpha),
+            Screen::Paused => {
+                /* frozen in place, so draw the last tick as it is */
+                self.game.draw(renderer, 1.0);
+                text::draw_centered(renderer, "PAUSED", 0.0, -60.0, 30.0, COLOR);
+            },
+            Screen::GameOver => {
+                self.game.draw(renderer, alpha);
+                text::draw_centered(renderer, &format!("PRESS {}", label(input, Key::Start)), 0.0, -60.0, 20.0, COLOR);
+            },
+            Screen::HighScore => {
+                text::draw_centered(renderer, "NEW HIGH SCORE", 0.0, 60.0, 30.0, COLOR);
+                text::draw_centered(renderer, &self.game.best_score().to_string(), 0.0, 10.0, 20.0, COLOR);
+
+                let name: String = self.name.iter().map(|&i| LETTERS[i] as char).collect();
+                let size = 30.0;
+                let left = -text::width(&name, size) / 2.0;
+                text::draw(renderer, &name, left, -50.0, size, COLOR);
+                let under = left + text::width(&name[..self.cursor], size) + if self.cursor > 0 { size / 3.0 } else { 0.0 };
+                text::draw(renderer, "_", under, -60.0, size, COLOR);
+            },
+            Screen::Controls => {
+                let editing = self.editing.min(inputs.len() - 1);
+                if inputs.len() > 1 {
+                    let title = format!("PLAYER {} CONTROLS", editing + 1);
+                    text::draw_centered(renderer, &title, 0.0, 150.0, 30.0, COLOR);
+                } else {
+                    text::draw_centered(renderer, "CONTROLS", 0.0, 150.0, 30.0, COLOR);
+                }
+
+                let size = 16.0;
+                for (i, key) in KEYS.iter().enumerate() {
+                    let y = 100.0 - 30.0 * i as f32;
+                    let names: Vec<String> = inputs[editing].keymap().bindings_of(*key).iter()
+                        .map(|b| b.name().to_uppercase())
+                        .collect();
+                    if i == self.selected {
+                        text::draw(renderer, ">", -220.0, y, size, COLOR);
+                    }
+                    text::draw(renderer, &key.name().to_uppercase(), -190.0, y, size, COLOR);
+                    text::draw(renderer, &names.join(" "), -40.0, y, size, COLOR);
+                }
+
+                let help = if self.capturing {
+                    format!("PRESS A KEY FOR {}", KEYS[self.selected].name().to_uppercase())
+                } else if inputs.len() > 1 {
+                    format!("{} ADD KEY  {} REMOVE KEY  {} {} PLAYER  {} BACK",
+                            label(input, Key::Start), label(input, Key::Pause),
+                            label(input, Key::Left), label(input, Key::Right), label(input, Key::Quit))
+                } else {
+                    format!("{} ADD KEY  {} REMOVE KEY  {} BACK",
+                            label(input, Key::Start), label(input, Key::Pause), label(input, Key::Quit))
+                };
+                text::draw_centered(renderer, &help, 0.0, -180.0, 12.0, COLOR);
+            },
+        }
+    }
+}
+
+#[test]
+fn test_screens() {
+    use ship::Ship;
+
+    let mut app = App::new(7, World::default(), Rc::new(ShipMeta::default()), Vec::new());
+    let mut inputs = vec![Input::new()];
+    let dt = 1.0 / 60.0;
+
+    let mut tap = |app: &mut App, key| {
+        inputs[0].set(key, true);
+        let running = app.think(dt, &mut inputs);
+        inputs[0].set(key, false);
+        app.think(dt, &mut inputs);
+        running
+    };
+
+    assert_eq!(app.screen(), Screen::Title);
+    tap(&mut app, Key::Start);
+    assert_eq!(app.screen(), Screen::Playing);
+
+    /* nothing moves while paused */
+    tap(&mut app, Key::Pause);
+    assert_eq!(app.screen(), Screen::Paused);
+    let player = app.game().player(0);
+    let p = app.game().engine.get_as::<Ship>(player).unwrap().body.p;
+    for _ in 0..60 {
+        app.think(dt, &mut [Input::new()]);
+    }
+    assert_eq!(app.game().engine.get_as::<Ship>(player).unwrap().body.p, p);
+    tap(&mut app, Key::Pause);
+    assert_eq!(app.screen(), Screen::Playing);
+
+    /* lose every ship to end the game */
+    app.game().engine.get_as::<Ship>(player).unwrap().score = 500;
+    app.think(dt, &mut [Input::new()]);
+    while app.screen() == Screen::Playing {
+        let player = app.game().player(0);
+        if let Some(ship) = app.game().engine.get_as::<Ship>(player) {
+            ship.health = 0.0;
+        }
+        app.think(dt, &mut [Input::new()]);
+    }
+    assert_eq!(app.screen(), Screen::GameOver);
+
+    /* enter "BZA" */
+    tap(&mut app, Key::Start);
+    assert_eq!(app.screen(), Screen::HighScore);
+    tap(&mut app, Key::Forward);
+    tap(&mut app, Key::Right);
+    tap(&mut app, Key::Reverse);
+    tap(&mut app, Key::Start);
+    assert_eq!(app.screen(), Screen::Title);
+    assert_eq!(app.scores(), &[("BZA".to_owned(), 500)]);
+
+    /* a new game starts from scratch */
+    tap(&mut app, Key::Start);
+    assert_eq!(app.screen(), Screen::Playing);
+    assert_eq!(app.game().score(0), 0);
+    assert_eq!(app.game().lives(0), 3);
+
+    tap(&mut app, Key::Quit);
+    assert_eq!(app.screen(), Screen::Title);
+    assert!(!tap(&mut app, Key::Quit));
+}
+
+#[test]
+fn test_rebinding() {
+    use glutin::{ Event, ElementState, VirtualKeyCode as Code };
+    use keymap::{ Binding, Keymap };
+
+    /* every player's input sees every key, as they share the keyboard */
+    fn press(app: &mut App, inputs: &mut [Input], code: Code) {
+        let dt = 1.0 / 60.0;
+        for state in &[ElementState::Pressed, ElementState::Released] {
+            for input in inputs.iter_mut() {
+                input.handle_event(&Event::KeyboardInput(*state, 0, Some(code)));
+            }
+            app.think(dt, inputs);
+        }
+    }
+
+    let mut app = App::new(7, World::default(), Rc::new(ShipMeta::default()), Vec::new());
+    app.set_players(2, Mode::Coop);
+    let mut inputs = vec![Input::new(), Input::with_keymap(Keymap::for_player(1))];
+
+    press(&mut app, &mut inputs, Code::P);
+    assert_eq!(app.screen(), Screen::Controls);
+
+    /* down to fire, and bind right control to it */
+    for _ in 0..4 {
+        press(&mut app, &mut inputs, Code::Down);
+    }
+    press(&mut app, &mut inputs, Code::Return);
+    press(&mut app, &mut inputs, Code::RControl);
+    assert_eq!(inputs[0].keymap().bindings_of(Key::Fire), vec![Binding::new(Code::Space), Binding::new(Code::RControl)]);
+
+    /* drop space, the new key stays */
+    press(&mut app, &mut inputs, Code::P);
+    press(&mut app, &mut inputs, Code::P);
+    assert_eq!(inputs[0].keymap().bindings_of(Key::Fire), vec![Binding::new(Code::RControl)]);
+
+    /* over to the second player, who gets q for fire */
+    press(&mut app, &mut inputs, Code::Right);
+    press(&mut app, &mut inputs, Code::Return);
+    press(&mut app, &mut inputs, Code::Q);
+    assert_eq!(inputs[1].keymap().bindings_of(Key::Fire), vec![Binding::new(Code::Tab), Binding::new(Code::Q)]);
+    assert_eq!(inputs[0].keymap().lookup(Code::Q, 0), None);
+
+    press(&mut app, &mut inputs, Code::Escape);
+    assert_eq!(app.screen(), Screen::Title);
+    inputs[0].handle_event(&Event::KeyboardInput(ElementState::Pressed, 0, Some(Code::RControl)));
+    assert!(inputs[0].pressed(Key::Fire));
+
+    /* a replay stays on the title where the recording went to the
+     * controls screen, as the ticks spent there weren't recorded */
+    app.set_replaying(true);
+    press(&mut app, &mut inputs, Code::P);
+    assert_eq!(app.screen(), Screen::Title);
+}
@@ -1,59 +1,68 @@
-use physics::{ Body, V32 };
-use input::Input;
-use hud::Hud;
-use entity::{ State, Entity };
-use render;
+use physics::{ layer, Body, Contact, Filter, V32 };
+use input::Snapshot;
+use event::Kind;
+use entity::{ State, Entity, Random, Spawner };
+use render::Render;
 use boom::Boom;
+use world::World;
 
 pub struct Beam {
     body: Body,
     state: State,
-    spawn: Option<V32>,
+    hit: Option<V32>,
 }
 
 impl Beam {
     pub fn new(body: Body) -> Beam {
         Beam {
-            body: body,
+            body: Body {
+                bullet: true,
+                filter: Filter::new(layer::PROJECTILE, layer::ALL & !layer::PROJECTILE),
+                ..body
+            },
             state: State::Alive,
-            spawn: None,
+            hit: None,
         }
     }
+
+    /// Fly through ships instead of hitting them.
+    pub fn sparing_ships(self) -> Beam {
+        let mut beam = self;
+        beam.body.filter.mask &= !layer::SHIP;
+        beam
+    }
 }
 
 impl Entity for Beam {
-    fn draw(&mut self, renderer: &mut render::Renderer) {
+    fn draw(&mut self, renderer: &mut Render, world: &World, alpha: f32) {
         let r = self.body.r;
         let mut shape = renderer.create_shape_simple(
             [ 0.3, 0.9, 0.6, 1.0 ],
             &[ [0.0, r], [1.0, r] ]);
-        shape.set_transform(self.body.p.x, self.body.p.y, self.body.a);
-        renderer.draw_shape(&mut shape);
+        let a = self.body.lerp_a(alpha);
+        for p in world.images(self.body.lerp_p(alpha), r) {
+            shape.set_transform(p.x, p.y, a);
+            renderer.draw_shape(&mut shape);
+        }
     }
 
-    fn think(&mut self, dt: f32, _input: &Input, _hud: &mut Hud, spawn: &mut Vec<Box<Entity>>) -> State {
-        self.body.think(dt);
+    fn think(&mut self, dt: f32, _: &[Snapshot], world: &World, rng: &mut Random, spawn: &mut Spawner) -> State {
+        if let Some(p) = self.hit.take() {
+            spawn.push(Box::new(Boom::new(rng, p.x, p.y, self.body.a)));
+        }
+
+        self.body.think(dt, world);
 
         self.body.r -= dt;
         if self.body.r < 0.0 {
             self.state = State::Dead;
         }
 
-        if let Some(p) = self.spawn.take() {
-            spawn.push(Box::new(Boom::new(p.x, p.y, self.body.a)));
-        }
-
         self.state
     }
 
-    fn collide(&mut self, other: &mut Entity, _energy: f32) {
-        use cgmath::EuclideanVector;
-        if other.is_beam() {
-            return;
-        }
-        if let Some(ob) = other.body() {
-            self.spawn = Some(ob.p + (self.body.p - ob.p).normalize() * ob.r);
-        }
+    fn collide(&mut self, _other: &mut Entity, contact: &Contact) {
+        self.hit = Some(contact.point);
         self.state = State::Dead;
     }
 
@@ -61,5 +70,44 @@ impl Entity for Beam {
         Some(&mut self.body)
     }
 
-    fn is_beam(&self) -> bool { true }
+    fn kind(&self) -> Kind {
+        Kind::Projectile
+    }
+}
+
+#[test]
+fn test_beam_hits_thin_target() {
+    use cgmath::vec2;
+    use entity::Engine;
+    use roid::{ Generator, SizeClass };
+
+    fn run(bullet: bool) -> bool {
+        let mut engine = Engine::new(1, World::default());
+        let input = [Snapshot::new()];
+        let dt = 1.0 / 30.0;
+
+        let gen = Generator {
+            sizes: vec![ SizeClass { radius: (10.0, 10.0), weight: 1.0 } ],
+            speed: (0.0, 0.0),
+            spin: (0.0, 0.0),
+            ..Generator::new()
+        };
+        let roid = gen.create_at(engine.rng(), vec2(0.0, 0.0));
+
+        /* 40 units per step, stepping from -20 to 20, past the rock */
+        let mut beam = Beam::new(Body::init(Body { p: vec2(-100.0, 0.0), dp: vec2(1200.0, 0.0), r: 5.0, ..Default::default() }));
+        beam.body.bullet = bullet;
+
+        engine.add(Box::new(roid));
+        engine.add(Box::new(beam));
+
+        for _ in 0..10 {
+            engine.think(dt, &input);
+        }
+
+        engine.iter_mut().all(|(_, e)| e.body().map_or(true, |b| b.filter.category != layer::PROJECTILE))
+    }
+
+    assert!(run(true));
+    assert!(!run(false));
 }
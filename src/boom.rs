@@ -1,9 +1,10 @@
-use entity::{ State, Entity };
+use entity::{ State, Entity, Random, Spawner };
 use physics;
-use render;
-use hud::Hud;
-use input::Input;
-use rand::{ thread_rng, Rng };
+use render::Render;
+use event::Kind;
+use input::Snapshot;
+use rand::Rng;
+use world::World;
 
 pub struct Boom {
     x: f32,
@@ -13,8 +14,7 @@ pub struct Boom {
 }
 
 impl Boom {
-    pub fn new(x: f32, y: f32, a: f32) -> Boom {
-        let mut rng = thread_rng();
+    pub fn new(rng: &mut Random, x: f32, y: f32, a: f32) -> Boom {
         Boom {
             x: x + rng.gen_range(-5.0, 5.0),
             y: y + rng.gen_range(-5.0, 5.0),
@@ -25,11 +25,11 @@ impl Boom {
 }
 
 impl Entity for Boom {
-    fn draw(&mut self, renderer: &mut render::Renderer) {
+    fn draw(&mut self, renderer: &mut Render, _: &World, _: f32) {
         renderer.draw_boom(self.x, self.y, self.a, 10.0, self.t);
     }
 
-    fn think(&mut self, dt: f32, _: &Input, _: &mut Hud, _: &mut Vec<Box<Entity>>) -> State {
+    fn think(&mut self, dt: f32, _: &[Snapshot], _: &World, _: &mut Random, _: &mut Spawner) -> State {
         self.t += dt;
 
         if self.t < 0.5 {
@@ -39,6 +39,7 @@ impl Entity for Boom {
         }
     }
 
-    fn collide(&mut self, _: &mut Entity, _: f32) {}
+    fn collide(&mut self, _: &mut Entity, _: &physics::Contact) {}
     fn body(&mut self) -> Option<&mut physics::Body> { None }
+    fn kind(&self) -> Kind { Kind::Effect }
 }
@@ -0,0 +1,163 @@
+use cgmath::vec2;
+
+use physics::V32;
+use world::World;
+
+/// Position and radius of something that can collide.
+pub type Proxy = Option<(V32, f32)>;
+
+/// Every pair of proxies, `(i, j)` with `j < i`, in the order the brute
+/// force loop visits them.
+pub fn brute_force(proxies: &[Proxy]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for i in 0..proxies.len() {
+        if proxies[i].is_none() {
+            continue;
+        }
+        for j in 0..i {
+            if proxies[j].is_some() {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+/// Uniform grid over the world.
+///
+/// Bodies are put in every cell their bounding box overlaps. Along
+/// wrapping axes cell coordinates wrap around the edges, so bodies
+/// straddling an edge end up next to their neighbours on the other side.
+pub struct Grid {
+    lo: V32,
+    size: V32,
+    wrap_x: bool,
+    wrap_y: bool,
+
+    cols: usize,
+    rows: usize,
+    cell: V32,
+    cells: Vec<Vec<usize>>,
+}
+
+impl Grid {
+    pub fn new(world: &World) -> Grid {
+        let size = world.hi() - world.lo();
+        Grid {
+            lo: world.lo(),
+            size: size,
+            wrap_x: world.wrap_x,
+            wrap_y: world.wrap_y,
+            cols: 1,
+            rows: 1,
+            cell: size,
+            cells: vec![Vec::new()],
+        }
+    }
+
+    /* pick a cell size fitting the bodies, cells must tile the world exactly */
+    fn resize(&mut self, proxies: &[Proxy]) {
+        let (n, sum) = proxies.iter()
+            .filter_map(|p| p.map(|p| p.1))
+            .fold((0, 0.0), |(n, sum), r| (n + 1, sum + r));
+        let mean = if n > 0 { sum / n as f32 } else { 1.0 };
+
+        let want = (mean * 4.0).max(self.size.x.max(self.size.y) / 128.0);
+        let cols = ((self.size.x / want) as usize).max(1);
+        let rows = ((self.size.y / want) as usize).max(1);
+
+        if cols != self.cols || rows != self.rows {
+            self.cols = cols;
+            self.rows = rows;
+            self.cells = vec![Vec::new(); cols * rows];
+        }
+        self.cell = vec2(self.size.x / cols as f32, self.size.y / rows as f32);
+
+        for c in self.cells.iter_mut() {
+            c.clear();
+        }
+    }
+
+    /* range of cells covered by [a, b] along one axis */
+    fn span(a: f32, b: f32, cell: f32, n: usize, wrap: bool) -> (i32, i32) {
+        let n = n as i32;
+        let lo = (a / cell).floor() as i32;
+        let hi = (b / cell).floor() as i32;
+        if wrap {
+            (lo, hi.min(lo + n - 1))
+        } else {
+            (lo.max(0).min(n - 1), hi.max(0).min(n - 1))
+        }
+    }
+
+    /// Pairs of proxies that may touch, same format and order as `brute_force`.
+    pub fn pairs(&mut self, proxies: &[Proxy]) -> Vec<(usize, usize)> {
+        self.resize(proxies);
+
+        let (cols, rows) = (self.cols as i32, self.rows as i32);
+        for (i, proxy) in proxies.iter().enumerate() {
+            if let Some((p, r)) = *proxy {
+                let p = p - self.lo;
+                let (x0, x1) = Grid::span(p.x - r, p.x + r, self.cell.x, self.cols, self.wrap_x);
+                let (y0, y1) = Grid::span(p.y - r, p.y + r, self.cell.y, self.rows, self.wrap_y);
+
+                for y in y0..y1 + 1 {
+                    for x in x0..x1 + 1 {
+                        let (x, y) = (((x % cols) + cols) % cols, ((y % rows) + rows) % rows);
+                        self.cells[(y * cols + x) as usize].push(i);
+                    }
+                }
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for c in self.cells.iter() {
+            for (n, &i) in c.iter().enumerate() {
+                for &j in c[..n].iter() {
+                    /* cells are filled in index order, so j < i */
+                    pairs.push((i, j));
+                }
+            }
+        }
+
+        pairs.sort();
+        pairs.dedup();
+        pairs
+    }
+}
+
+#[cfg(test)]
+fn random_field(n: usize) -> Vec<Proxy> {
+    use rand::Rng;
+    use entity::seeded;
+
+    let mut rng = seeded(6);
+    (0..n).map(|i| {
+        if i % 17 == 0 {
+            None
+        } else {
+            let p = vec2(rng.gen_range(-350.0, 350.0), rng.gen_range(-350.0, 350.0));
+            let r = if i % 50 == 0 { rng.gen_range(30.0, 60.0) } else { rng.gen_range(2.0, 10.0) };
+            Some((p, r))
+        }
+    }).collect()
+}
+
+#[test]
+fn test_grid_matches_brute_force() {
+    let field = random_field(1500);
+
+    for world in &[World::default(), World::default().walled()] {
+        let touching = |&(i, j): &(usize, usize)| {
+            let (a, b) = (field[i].unwrap(), field[j].unwrap());
+            world.distance(a.0, b.0) < a.1 + b.1
+        };
+
+        let brute: Vec<_> = brute_force(&field).into_iter().filter(&touching).collect();
+        let mut grid = Grid::new(world);
+        let fast: Vec<_> = grid.pairs(&field).into_iter().filter(&touching).collect();
+
+        assert!(brute.len() > 0);
+        assert!(brute == fast);
+    }
+}
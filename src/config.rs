@@ -0,0 +1,338 @@
+//! Reader for the small subset of TOML used by the data files.
+//!
+//! Supported are `[section]` headers, `key = value` pairs and `#` comments,
+//! values are numbers, booleans, double quoted strings and flat lists.
+
+use std::fmt;
+use std::error;
+use std::fs::File;
+use std::io::Read;
+use std::mem;
+use std::path::Path;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Num(f64),
+    Bool(bool),
+    Str(String),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match *self {
+            Value::Num(_) => "a number",
+            Value::Bool(_) => "a boolean",
+            Value::Str(_) => "a string",
+            Value::List(_) => "a list",
+        }
+    }
+}
+
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+impl Entry {
+    pub fn error(&self, msg: String) -> Error {
+        Error::new(self.line, msg)
+    }
+
+    pub fn num(&self) -> Result<f64, Error> {
+        match self.value {
+            Value::Num(v) => Ok(v),
+            ref other => Err(self.error(format!("`{}` should be a number, not {}", self.key, other.kind()))),
+        }
+    }
+
+    /// Number within `lo..hi`, inclusive.
+    pub fn num_in(&self, lo: f64, hi: f64) -> Result<f64, Error> {
+        let v = try!(self.num());
+        if !v.is_finite() || v < lo || v > hi {
+            return Err(self.error(format!("`{}` is {}, should be between {} and {}", self.key, v, lo, hi)));
+        }
+        Ok(v)
+    }
+
+    /// Pair of numbers `[lo, hi]`, both within `min..max` and in order.
+    pub fn range_in(&self, min: f64, max: f64) -> Result<(f64, f64), Error> {
+        let items = match self.value {
+            Value::List(ref items) => items,
+            ref other => return Err(self.error(format!("`{}` should be a list, not {}", self.key, other.kind()))),
+        };
+        let nums: Vec<f64> = items.iter()
+            .filter_map(|v| match *v { Value::Num(n) => Some(n), _ => None })
+            .collect();
+        if items.len() != 2 || nums.len() != 2 {
+            return Err(self.error(format!("`{}` should be a pair of numbers [low, high]", self.key)));
+        }
+        let (lo, hi) = (nums[0], nums[1]);
+        if !lo.is_finite() || !hi.is_finite() || lo < min || hi > max || lo > hi {
+            return Err(self.error(format!("`{}` is [{}, {}], should be in order between {} and {}", self.key, lo, hi, min, max)));
+        }
+        Ok((lo, hi))
+    }
+
+    pub fn bool(&self) -> Result<bool, Error> {
+        match self.value {
+            Value::Bool(v) => Ok(v),
+            ref other => Err(self.error(format!("`{}` should be a boolean, not {}", self.key, other.kind()))),
+        }
+    }
+
+    pub fn str(&self) -> Result<&str, Error> {
+        match self.value {
+            Value::Str(ref v) => Ok(v),
+            ref other => Err(self.error(format!("`{}` should be a string, not {}", self.key, other.kind()))),
+        }
+    }
+
+    /// Error for a key the reader does not know about, suggesting the
+    /// closest of the known ones.
+    pub fn unknown(&self, section: &str, known: &[&str]) -> Error {
+        let best = known.iter()
+            .map(|k| (distance(k, &self.key), k))
+            .min();
+
+        let mut msg = format!("unknown field `{}` in [{}]", self.key, section);
+        if let Some((d, k)) = best {
+            if d <= 3 || k.contains(&self.key[..]) {
+                msg.push_str(&format!(", did you mean `{}`?", k));
+            }
+        }
+        self.error(msg)
+    }
+}
+
+pub struct Section {
+    pub name: String,
+    pub line: usize,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl Error {
+    pub fn new(line: usize, msg: String) -> Error {
+        Error {
+            line: line,
+            msg: msg,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "line {}: {}", self.line, self.msg)
+        } else {
+            write!(f, "{}", self.msg)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        &self.msg
+    }
+}
+
+/* edit distance, for suggestions */
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let cur = row[j + 1];
+            let cost = if ca == b[j] { 0 } else { 1 };
+            row[j + 1] = (prev + cost).min(row[j] + 1).min(cur + 1);
+            prev = cur;
+        }
+    }
+
+    row[b.len()]
+}
+
+fn parse_value(text: &str, line: usize) -> Result<Value, Error> {
+    let text = text.trim();
+
+    if text.starts_with('[') {
+        if !text.ends_with(']') {
+            return Err(Error::new(line, format!("unterminated list: {}", text)));
+        }
+        let inner = text[1..text.len() - 1].trim();
+        let mut items = Vec::new();
+        if !inner.is_empty() {
+            for item in inner.split(',') {
+                if item.trim().is_empty() {
+                    continue;
+                }
+                items.push(try!(parse_value(item, line)));
+            }
+        }
+        return Ok(Value::List(items));
+    }
+
+    if text.starts_with('"') {
+        if text.len() < 2 || !text.ends_with('"') {
+            return Err(Error::new(line, format!("unterminated string: {}", text)));
+        }
+        return Ok(Value::Str(text[1..text.len() - 1].to_owned()));
+    }
+
+    match text {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => (),
+    }
+
+    text.replace('_', "").parse()
+        .map(Value::Num)
+        .map_err(|_| Error::new(line, format!("can't understand value: {}", text)))
+}
+
+/* cut off a comment, unless the # is inside a string */
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+pub fn parse(text: &str) -> Result<Vec<Section>, Error> {
+    let mut sections = vec![Section {
+        name: String::new(),
+        line: 0,
+        entries: Vec::new(),
+    }];
+
+    for (n, line) in text.lines().enumerate() {
+        let n = n + 1;
+        let line = strip_comment(line).trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            if !line.ends_with(']') {
+                return Err(Error::new(n, format!("bad section header: {}", line)));
+            }
+            let name = line[1..line.len() - 1].trim().to_owned();
+            if sections.iter().any(|s| s.name == name) {
+                return Err(Error::new(n, format!("duplicate section [{}]", name)));
+            }
+            sections.push(Section {
+                name: name,
+                line: n,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let eq = match line.find('=') {
+            Some(eq) => eq,
+            None => return Err(Error::new(n, format!("expected `key = value`: {}", line))),
+        };
+
+        let key = line[..eq].trim().to_owned();
+        let value = try!(parse_value(&line[eq + 1..], n));
+
+        let section = sections.last_mut().unwrap();
+        if section.entries.iter().any(|e| e.key == key) {
+            return Err(Error::new(n, format!("duplicate field `{}`", key)));
+        }
+        section.entries.push(Entry {
+            key: key,
+            value: value,
+            line: n,
+        });
+    }
+
+    /* drop the top level if nothing was put there */
+    if sections[0].entries.is_empty() {
+        sections.remove(0);
+    }
+
+    Ok(sections)
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<Section>, Error> {
+    let path = path.as_ref();
+    let mut text = String::new();
+    try!(File::open(path)
+         .and_then(|mut f| f.read_to_string(&mut text))
+         .map_err(|e| Error::new(0, format!("can't read {}: {}", path.display(), e))));
+    parse(&text)
+}
+
+/// Fingerprint of numbers read from a data file, FNV-1a over their
+/// bits, to tell whether two runs were set up the same.
+pub fn digest(values: &[f64]) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for v in values {
+        let bits: u64 = unsafe { mem::transmute(*v) };
+        for i in 0..8 {
+            hash ^= (bits >> (i * 8)) as u8 as u32;
+            hash = hash.wrapping_mul(0x01000193);
+        }
+    }
+    hash
+}
+
+#[test]
+fn test_parse() {
+    let sections = parse(r#"
+        top = 1
+        # comment
+        [one]
+        a = 1.5       # trailing comment
+        b = "x # y"
+        c = [ "p", "q" ]
+        d = true
+        e = 1_000
+
+        [two]
+    "#).unwrap();
+
+    assert_eq!(sections.len(), 3);
+    assert_eq!(sections[0].name, "");
+    assert_eq!(sections[1].name, "one");
+    assert_eq!(sections[1].line, 4);
+
+    let e = &sections[1].entries;
+    assert_eq!(e[0].value, Value::Num(1.5));
+    assert_eq!(e[1].value, Value::Str("x # y".to_owned()));
+    assert_eq!(e[2].value, Value::List(vec![Value::Str("p".to_owned()), Value::Str("q".to_owned())]));
+    assert_eq!(e[3].value, Value::Bool(true));
+    assert_eq!(e[4].value, Value::Num(1000.0));
+    assert_eq!(e[4].line, 9);
+
+    assert_eq!(parse("[a]\nx = ?").err().unwrap().line, 2);
+    assert_eq!(parse("[a]\n[a]").err().unwrap().line, 2);
+    assert_eq!(parse("x = 1\nx = 2").err().unwrap().line, 2);
+}
+
+#[test]
+fn test_not_finite() {
+    let sections = parse("a = nan\nb = [0, nan]\nc = inf\nd = 0.5").unwrap();
+    let e = &sections[0].entries;
+    assert!(e[0].num_in(0.0, 1.0).is_err());
+    assert!(e[1].range_in(0.0, 1.0).is_err());
+    assert!(e[2].num_in(0.0, 1.0).is_err());
+    assert_eq!(e[3].num_in(0.0, 1.0).unwrap(), 0.5);
+}
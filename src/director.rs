@@ -0,0 +1,304 @@
+//! Waves of asteroids, one after another as the field is cleared.
+//!
+//! The first waves come from a data file, past the last of those each
+//! wave is a little harder than the one before.
+
+use entity::Engine;
+use hud::Hud;
+use physics::V32;
+use roid::{ Generator, Material, Roid, SizeClass };
+use config::{ self, Section };
+
+/* seconds between clearing the field and the next wave, and how long
+ * the wave number stays on screen */
+const WAVE_DELAY: f32 = 3.0;
+const ANNOUNCE_TIME: f32 = 2.0;
+
+/* procedural waves stop getting harder here */
+const MAX_COUNT: usize = 24;
+const MAX_RADIUS: f32 = 90.0;
+const MAX_SPEED: f32 = 200.0;
+const MAX_SPIN: f32 = 2.0;
+
+/// What one wave is made of.
+#[derive(Copy, Clone, Debug)]
+pub struct Wave {
+    pub count: usize,
+    pub radius: (f32, f32),
+    /* initial speed and spin, as in `Generator` */
+    pub speed: (f32, f32),
+    pub spin: (f32, f32),
+    /* relative amounts of each material */
+    pub rock: f32,
+    pub ice: f32,
+    pub iron: f32,
+}
+
+impl Default for Wave {
+    fn default() -> Wave {
+        Wave {
+            count: 4,
+            radius: (25.0, 50.0),
+            speed: (5.0, 20.0),
+            spin: (0.0, 0.1),
+            rock: 6.0,
+            ice: 3.0,
+            iron: 0.0,
+        }
+    }
+}
+
+/* field name, minimum, maximum */
+const WAVE_FIELDS: &'static [(&'static str, f64, f64)] = &[
+    ("count", 1.0, 100.0),
+    ("radius", 10.0, 200.0),
+    ("speed", 0.0, 1e3),
+    ("spin", 0.0, 10.0),
+    ("rock", 0.0, 100.0),
+    ("ice", 0.0, 100.0),
+    ("iron", 0.0, 100.0),
+];
+
+impl Wave {
+    /// Read one wave, fields not mentioned keep their default values.
+    pub fn from_section(section: &Section) -> Result<Wave, config::Error> {
+        let mut wave = Wave::default();
+        let names: Vec<_> = WAVE_FIELDS.iter().map(|f| f.0).collect();
+
+        for entry in section.entries.iter() {
+            let &(_, lo, hi) = match WAVE_FIELDS.iter().find(|f| f.0 == entry.key) {
+                Some(field) => field,
+                None => return Err(entry.unknown(&section.name, &names)),
+            };
+
+            match &entry.key[..] {
+                "count" => wave.count = try!(entry.num_in(lo, hi)) as usize,
+                "radius" => wave.radius = range(try!(entry.range_in(lo, hi))),
+                "speed" => wave.speed = range(try!(entry.range_in(lo, hi))),
+                "spin" => wave.spin = range(try!(entry.range_in(lo, hi))),
+                "rock" => wave.rock = try!(entry.num_in(lo, hi)) as f32,
+                "ice" => wave.ice = try!(entry.num_in(lo, hi)) as f32,
+                "iron" => wave.iron = try!(entry.num_in(lo, hi)) as f32,
+                _ => unreachable!(),
+            }
+        }
+
+        if wave.rock + wave.ice + wave.iron <= 0.0 {
+            return Err(config::Error::new(section.line, format!("wave [{}] has no rock, ice or iron in it", section.name)));
+        }
+
+        Ok(wave)
+    }
+
+    /// Read every wave in a data file, in order, one wave per section.
+    pub fn load_waves(sections: &[Section]) -> Result<Vec<Wave>, config::Error> {
+        let mut waves = Vec::new();
+        for section in sections {
+            if section.name.is_empty() {
+                let line = section.entries[0].line;
+                return Err(config::Error::new(line, "wave fields must be inside a [wave] section".to_owned()));
+            }
+            waves.push(try!(Wave::from_section(section)));
+        }
+        Ok(waves)
+    }
+
+    /// Fingerprint of a list of waves, for replays to check they play
+    /// with the waves they were recorded with.
+    pub fn digest_waves(waves: &[Wave]) -> u32 {
+        let mut values = Vec::new();
+        for wave in waves {
+            values.extend_from_slice(&[
+                wave.count as f64,
+                wave.radius.0 as f64, wave.radius.1 as f64,
+                wave.speed.0 as f64, wave.speed.1 as f64,
+                wave.spin.0 as f64, wave.spin.1 as f64,
+                wave.rock as f64, wave.ice as f64, wave.iron as f64,
+            ]);
+        }
+        config::digest(&values)
+    }
+
+    /// The wave after this one: more, bigger, faster and heavier rocks.
+    pub fn harder(&self) -> Wave {
+        let grow = |(lo, hi): (f32, f32), by: f32, max: f32| ((lo * by).min(max), (hi * by).min(max));
+        Wave {
+            count: (self.count + 1).min(MAX_COUNT).max(self.count),
+            radius: grow(self.radius, 1.05, MAX_RADIUS),
+            speed: grow(self.speed, 1.1, MAX_SPEED),
+            spin: grow(self.spin, 1.1, MAX_SPIN),
+            iron: self.iron + 0.5,
+            ..*self
+        }
+    }
+
+    pub fn generator(&self) -> Generator {
+        let mut materials = Vec::new();
+        for &(material, weight) in [(Material::rock(), self.rock), (Material::ice(), self.ice), (Material::iron(), self.iron)].iter() {
+            if weight > 0.0 {
+                materials.push((material, weight));
+            }
+        }
+
+        Generator {
+            sizes: vec![ SizeClass { radius: self.radius, weight: 1.0 } ],
+            materials: materials,
+            speed: self.speed,
+            spin: self.spin,
+            ..Generator::new()
+        }
+    }
+}
+
+fn range((lo, hi): (f64, f64)) -> (f32, f32) {
+    (lo as f32, hi as f32)
+}
+
+/// Sends in the next wave whenever the field is clear of asteroids.
+pub struct Director {
+    waves: Vec<Wave>,
+    /* the wave in play, 0 before the first */
+    number: usize,
+    /* time left until the next wave, once the field is clear */
+    next: Option<f32>,
+    /* time left showing the last announcement */
+    message_time: f32,
+}
+
+impl Director {
+    /// Play `waves` in order, then keep going with harder ones.
+    pub fn new(waves: Vec<Wave>) -> Director {
+        Director {
+            waves: waves,
+            number: 0,
+            next: None,
+            message_time: 0.0,
+        }
+    }
+
+    /// The wave in play, counting from 1.
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// What wave `n` is made of, counting from 1.
+    pub fn wave(&self, n: usize) -> Wave {
+        let n = n.max(1);
+        if n <= self.waves.len() {
+            return self.waves[n - 1];
+        }
+
+        let mut wave = self.waves.last().cloned().unwrap_or(Wave::default());
+        for _ in self.waves.len().max(1)..n {
+            wave = wave.harder();
+        }
+        wave
+    }
+
+    /// Send in the next wave now, keeping clear of `clear`.
+    pub fn start(&mut self, engine: &mut Engine, hud: &mut Hud, clear: V32) {
+        self.number += 1;
+        self.next = None;
+
+        let wave = self.wave(self.number);
+        let world = *engine.world();
+        for roid in wave.generator().populate(engine.rng(), &world, clear, wave.count) {
+            engine.add(Box::new(roid));
+        }
+
+        self.announce(hud, format!("WAVE {}", self.number));
+    }
+
+    pub fn think(&mut self, dt: f32, engine: &mut Engine, hud: &mut Hud, clear: V32) {
+        if self.message_time > 0.0 {
+            self.message_time -= dt;
+            if self.message_time <= 0.0 {
+                hud.set_message(None);
+            }
+        }
+
+        match self.next {
+            Some(t) if t > dt => self.next = Some(t - dt),
+            Some(_) => self.start(engine, hud, clear),
+            None => {
+                if engine.iter_of::<Roid>().next().is_none() {
+                    self.next = Some(WAVE_DELAY);
+                    self.announce(hud, format!("WAVE {} CLEAR", self.number));
+                }
+            }
+        }
+    }
+
+    fn announce(&mut self, hud: &mut Hud, message: String) {
+        hud.set_message(Some(&message));
+        self.message_time = ANNOUNCE_TIME;
+    }
+}
+
+#[test]
+fn test_waves() {
+    use cgmath::vec2;
+    use world::World;
+
+    let sections = config::parse("
+        [first]
+        count = 2
+        radius = [20, 30]
+
+        [second]
+        count = 3
+        speed = [10, 40]
+        iron = 1
+    ").unwrap();
+    let waves = Wave::load_waves(&sections).unwrap();
+    assert_eq!(waves.len(), 2);
+    assert_eq!(waves[0].radius, (20.0, 30.0));
+    assert_eq!(waves[1].speed, (10.0, 40.0));
+    assert!(Wave::digest_waves(&waves) != Wave::digest_waves(&waves[..1]));
+
+    let err = |text| Wave::load_waves(&config::parse(text).unwrap()).err().unwrap().to_string();
+    assert_eq!(err("[a]\nradius = [50, 20]"),
+               "line 2: `radius` is [50, 20], should be in order between 10 and 200");
+    assert_eq!(err("[a]\nspeed = 5"),
+               "line 2: `speed` should be a list, not a number");
+
+    /* past the scripted waves, each is harder than the last */
+    let mut director = Director::new(waves);
+    let (third, fourth) = (director.wave(3), director.wave(4));
+    assert_eq!(third.count, 4);
+    assert!(fourth.count > third.count);
+    assert!(fourth.speed.1 > third.speed.1);
+    assert!(fourth.radius.1 > third.radius.1);
+    assert!(fourth.iron > third.iron);
+
+    let mut engine = Engine::new(3, World::default());
+    let mut hud = Hud::new(&World::default(), 1);
+    let clear = vec2(0.0, 0.0);
+    let dt = 1.0 / 60.0;
+
+    director.start(&mut engine, &mut hud, clear);
+    assert_eq!(director.number(), 1);
+    assert_eq!(engine.iter_of::<Roid>().count(), 2);
+    assert_eq!(hud.message(), Some("WAVE 1"));
+
+    /* nothing happens while there is something left to shoot */
+    for _ in 0..(ANNOUNCE_TIME / dt).ceil() as usize + 1 {
+        director.think(dt, &mut engine, &mut hud, clear);
+    }
+    assert_eq!(director.number(), 1);
+    assert_eq!(hud.message(), None);
+
+    let ids: Vec<_> = engine.iter_of::<Roid>().map(|(id, _)| id).collect();
+    for id in ids {
+        engine.remove(id);
+    }
+    director.think(dt, &mut engine, &mut hud, clear);
+    assert_eq!(hud.message(), Some("WAVE 1 CLEAR"));
+
+    for _ in 0..(WAVE_DELAY / dt).ceil() as usize + 1 {
+        director.think(dt, &mut engine, &mut hud, clear);
+    }
+    assert_eq!(director.number(), 2);
+    assert_eq!(engine.iter_of::<Roid>().count(), 3);
+    assert_eq!(hud.message(), Some("WAVE 2"));
+}
@@ -0,0 +1,55 @@
+use entity::{ State, Entity, Random, Spawner };
+use physics::{ self, layer, Body, Filter };
+use render::Render;
+use event::Kind;
+use input::Snapshot;
+use rand::Rng;
+use world::World;
+
+/// Debris left by the smallest fragments of an asteroid.
+///
+/// Keeps drifting with the mass it was given, but does not collide with
+/// anything, and fades out after a while.
+pub struct Dust {
+    body: Body,
+    t: f32,
+    life: f32,
+}
+
+impl Dust {
+    pub fn new(rng: &mut Random, body: Body) -> Dust {
+        Dust {
+            body: Body { filter: Filter::new(layer::EFFECT, 0), ..body },
+            t: 0.0,
+            life: rng.gen_range(0.5, 1.5),
+        }
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.body.m
+    }
+}
+
+impl Entity for Dust {
+    fn draw(&mut self, renderer: &mut Render, _: &World, alpha: f32) {
+        let p = self.body.lerp_p(alpha);
+        /* the boom effect runs for half a second, stretch it over our life */
+        let t = self.t / self.life * 0.5;
+        renderer.draw_boom(p.x, p.y, self.body.lerp_a(alpha), self.body.r, t);
+    }
+
+    fn think(&mut self, dt: f32, _: &[Snapshot], world: &World, _: &mut Random, _: &mut Spawner) -> State {
+        self.body.think(dt, world);
+        self.t += dt;
+
+        if self.t < self.life {
+            State::Alive
+        } else {
+            State::Dead
+        }
+    }
+
+    fn collide(&mut self, _: &mut Entity, _: &physics::Contact) {}
+    fn body(&mut self) -> Option<&mut physics::Body> { None }
+    fn kind(&self) -> Kind { Kind::Effect }
+}
@@ -1,7 +1,25 @@
-use input::Input;
-use render;
-use physics;
-use hud::Hud;
+use std::any::Any;
+use std::iter::Enumerate;
+use std::slice;
+
+use rand::{ XorShiftRng, SeedableRng };
+
+use input::Snapshot;
+use render::Render;
+use physics::{ self, Contact };
+use event::{ Event, Kind };
+use broadphase::{ self, Grid };
+use world::World;
+
+/// Random numbers for the simulation.
+///
+/// Anything that affects the game state must draw from the engine's
+/// generator, so that a seed and an input stream reproduce a run.
+pub type Random = XorShiftRng;
+
+pub fn seeded(seed: u32) -> Random {
+    XorShiftRng::from_seed([ seed ^ 0x193a6754, 0xa8a7d469, 0x97830e05, 0x113ba7bb ])
+}
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum State {
@@ -9,85 +27,614 @@ pub enum State {
     Dead,
 }
 
-pub trait Entity {
-    fn draw(&mut self, renderer: &mut render::Renderer);
-    fn think(&mut self, dt: f32, &Input, hud: &mut Hud, born: &mut Vec<Box<Entity>>) -> State;
-    fn collide(&mut self, other: &mut Entity, energy: f32);
+/// Handle to an entity in the engine.
+///
+/// Slots are reused once their entity dies, the generation tells the
+/// old occupant's ids apart from the new one's.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+impl EntityId {
+    #[cfg(test)]
+    pub fn new(index: u32, generation: u32) -> EntityId {
+        EntityId {
+            index: index,
+            generation: generation,
+        }
+    }
+}
+
+/// Lets entities be looked up by their concrete type.
+pub trait AsAny {
+    fn as_any(&mut self) -> &mut Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&mut self) -> &mut Any {
+        self
+    }
+}
+
+pub trait Entity: AsAny {
+    fn draw(&mut self, renderer: &mut Render, world: &World, alpha: f32);
+    fn think(&mut self, dt: f32, &[Snapshot], world: &World, rng: &mut Random, born: &mut Spawner) -> State;
+    fn collide(&mut self, other: &mut Entity, contact: &Contact);
     fn body(&mut self) -> Option<&mut physics::Body>;
-    fn is_beam(&self) -> bool { false }
+    fn kind(&self) -> Kind;
+    /* for things that can be damaged */
+    fn health(&self) -> Option<f32> { None }
+}
+
+/// Takes entities spawned during a tick, they join the engine at the end of it.
+pub struct Spawner {
+    /* new entity, and the one that spawned it */
+    born: Vec<(EntityId, Option<EntityId>, Box<Entity>)>,
+    parent: Option<EntityId>,
+    /* ids for the free slots, and for the first slot past the end */
+    free: Vec<EntityId>,
+    next: u32,
+}
+
+impl Spawner {
+    fn new() -> Spawner {
+        Spawner {
+            born: Vec::new(),
+            parent: None,
+            free: Vec::new(),
+            next: 0,
+        }
+    }
+
+    pub fn push(&mut self, entity: Box<Entity>) -> EntityId {
+        let id = match self.free.pop() {
+            Some(id) => id,
+            None => {
+                self.next += 1;
+                EntityId { index: self.next - 1, generation: 0 }
+            }
+        };
+        self.born.push((id, self.parent, entity));
+        id
+    }
+}
+
+struct Slot {
+    generation: u32,
+    entity: Option<Box<Entity>>,
+
+    /* who to blame for the last hit and what kind of thing landed it,
+     * and health before it */
+    hit_by: Option<(EntityId, Kind)>,
+    health: Option<f32>,
 }
 
 pub struct Engine {
-    entities: Vec<Box<Entity>>,
+    slots: Vec<Slot>,
+    world: World,
+    rng: Random,
+    grid: Grid,
 
-    born: Vec<Box<Entity>>,
+    spawner: Spawner,
     dead: Vec<usize>,
+    events: Vec<Event>,
+    /* events of entities added between ticks, told with the next one */
+    added: Vec<Event>,
 }
 
-fn collide(a: &mut Entity, b: &mut Entity) -> Option<f32> {
-    if let (Some(i), Some(j)) = (a.body(), b.body()) {
-        physics::collide(i, j)
-    } else {
+fn collide(a: &mut Entity, b: &mut Entity, world: &World) -> Option<Contact> {
+    match (a.body(), b.body()) {
+        (Some(i), Some(j)) if physics::can_collide(i, j) => physics::collide(i, j, world),
+        _ => None,
+    }
+}
+
+/// Iterator over the live entities and their ids.
+pub struct IterMut<'a> {
+    slots: Enumerate<slice::IterMut<'a, Slot>>,
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (EntityId, &'a mut Box<Entity>);
+
+    fn next(&mut self) -> Option<(EntityId, &'a mut Box<Entity>)> {
+        while let Some((i, slot)) = self.slots.next() {
+            if let Some(ref mut e) = slot.entity {
+                return Some((EntityId { index: i as u32, generation: slot.generation }, e));
+            }
+        }
         None
     }
 }
 
 impl Engine
 {
-    pub fn new() -> Engine {
+    pub fn new(seed: u32, world: World) -> Engine {
         Engine {
-            entities: Vec::new(),
-            born: Vec::new(),
+            slots: Vec::new(),
+            world: world,
+            rng: seeded(seed),
+            grid: Grid::new(&world),
+            spawner: Spawner::new(),
             dead: Vec::new(),
+            events: Vec::new(),
+            added: Vec::new(),
         }
     }
 
-    pub fn add(&mut self, entity: Box<Entity>) {
-        self.entities.push(entity);
+    pub fn add(&mut self, entity: Box<Entity>) -> EntityId {
+        self.spawner.parent = None;
+        let id = self.spawner.push(entity);
+        let n = self.events.len();
+        self.add_born();
+        let spawned: Vec<_> = self.events.drain(n..).collect();
+        self.added.extend(spawned);
+        id
     }
 
-    pub fn draw(&mut self, renderer: &mut render::Renderer) {
-        for e in self.entities.iter_mut() {
-            e.draw(renderer);
+    fn add_born(&mut self) {
+        for (id, parent, mut entity) in self.spawner.born.drain(..) {
+            if let Some(body) = entity.body() {
+                body.id = Some(id);
+            }
+
+            let kind = entity.kind();
+            self.events.push(Event::Spawned { id: id, kind: kind, by: parent });
+            if let (Some(by), Kind::Projectile) = (parent, kind) {
+                self.events.push(Event::ShotFired { by: by, shot: id });
+            }
+
+            let slot = Slot {
+                generation: id.generation,
+                health: entity.health(),
+                entity: Some(entity),
+                hit_by: None,
+            };
+            let i = id.index as usize;
+            if i == self.slots.len() {
+                self.slots.push(slot);
+            } else {
+                self.slots[i] = slot;
+            }
+        }
+    }
+
+    fn remove_dead(&mut self) {
+        while let Some(i) = self.dead.pop() {
+            let slot = &mut self.slots[i];
+            if let Some(mut e) = slot.entity.take() {
+                self.events.push(Event::Destroyed {
+                    id: EntityId { index: i as u32, generation: slot.generation },
+                    kind: e.kind(),
+                    by: slot.hit_by.map(|h| h.0),
+                    with: slot.hit_by.map(|h| h.1),
+                    size: e.body().map_or(0.0, |b| b.r),
+                });
+            }
+            slot.generation += 1;
+            self.spawner.free.push(EntityId { index: i as u32, generation: slot.generation });
+        }
+    }
+
+    /// Take an entity out of the simulation at once, without reporting
+    /// it as destroyed.
+    pub fn remove(&mut self, id: EntityId) -> Option<Box<Entity>> {
+        if !self.contains(id) {
+            return None;
+        }
+        let slot = &mut self.slots[id.index as usize];
+        let e = slot.entity.take();
+        slot.generation += 1;
+        self.spawner.free.push(EntityId { index: id.index, generation: slot.generation });
+        e
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|s| s.entity.is_some()).count()
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn rng(&mut self) -> &mut Random {
+        &mut self.rng
+    }
+
+    /// What happened during the last tick, including the spawning of
+    /// entities added since the one before.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    fn id(&self, i: usize) -> EntityId {
+        EntityId { index: i as u32, generation: self.slots[i].generation }
+    }
+
+    /// Whether the entity is still around.
+    pub fn contains(&self, id: EntityId) -> bool {
+        match self.slots.get(id.index as usize) {
+            Some(slot) => slot.generation == id.generation && slot.entity.is_some(),
+            None => false,
         }
     }
 
-    fn collide_one(&mut self, i: usize) {
-        let (todo, mut rest) = self.entities.split_at_mut(i);
-        let mut this = rest[0].as_mut();
+    pub fn get(&mut self, id: EntityId) -> Option<&mut Entity> {
+        if !self.contains(id) {
+            return None;
+        }
+        match self.slots[id.index as usize].entity {
+            Some(ref mut e) => Some(&mut **e),
+            None => None,
+        }
+    }
 
-        for other in todo {
-            /* &** (&Box<Entity>) : &Entity */
-            if let Some(energy) = collide(this, &mut **other) {
-                this.collide(&mut **other, energy);
-                other.collide(this, energy);
+    /// The entity, if it is still around and is a `T`.
+    pub fn get_as<T: Entity + Any>(&mut self, id: EntityId) -> Option<&mut T> {
+        self.get(id).and_then(|e| e.as_any().downcast_mut::<T>())
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut {
+        IterMut { slots: self.slots.iter_mut().enumerate() }
+    }
+
+    /// Every entity of type `T`.
+    pub fn iter_of<'a, T: Entity + Any>(&'a mut self) -> Box<Iterator<Item = (EntityId, &'a mut T)> + 'a> {
+        Box::new(self.iter_mut().filter_map(|(id, e)| (**e).as_any().downcast_mut::<T>().map(|e| (id, e))))
+    }
+
+    pub fn draw(&mut self, renderer: &mut Render, alpha: f32) {
+        for slot in self.slots.iter_mut() {
+            if let Some(ref mut e) = slot.entity {
+                e.draw(renderer, &self.world, alpha);
             }
         }
     }
 
-    fn collide_all(&mut self) {
-        let len = self.entities.len();
-        for i in 0..len {
-            self.collide_one(i);
+    /* entities in two different occupied slots */
+    fn pair(&mut self, i: usize, j: usize) -> (&mut Entity, &mut Entity) {
+        let (lo, hi) = if i < j { (i, j) } else { (j, i) };
+        let (head, tail) = self.slots.split_at_mut(hi);
+        let (a, b) = (head[lo].entity.as_mut().unwrap(), tail[0].entity.as_mut().unwrap());
+        if i < j { (&mut **a, &mut **b) } else { (&mut **b, &mut **a) }
+    }
+
+    fn collide_pair(&mut self, i: usize, j: usize) {
+        let world = self.world;
+        let (a, b) = (self.id(i), self.id(j));
+
+        let (contact, blame_a, blame_b) = {
+            let (this, other) = self.pair(i, j);
+            match collide(this, other, &world) {
+                Some(contact) => {
+                    this.collide(other, &contact);
+                    other.collide(this, &contact.flip());
+                    /* the shooter gets the blame for its shots */
+                    let blame = |e: &mut Entity, id| Some((e.body().and_then(|b| b.owner).unwrap_or(id), e.kind()));
+                    (contact, blame(other, b), blame(this, a))
+                },
+                None => return,
+            }
+        };
+
+        self.slots[i].hit_by = blame_a;
+        self.slots[j].hit_by = blame_b;
+        self.events.push(Event::Collision { a: a, b: b, energy: contact.energy, point: contact.point });
+    }
+
+    fn body(&mut self, i: usize) -> Option<&mut physics::Body> {
+        self.slots[i].entity.as_mut().and_then(|e| e.body())
+    }
+
+    fn proxies(&mut self) -> Vec<broadphase::Proxy> {
+        use cgmath::EuclideanVector;
+
+        (0..self.slots.len())
+            .map(|i| self.body(i).map(|b| {
+                if b.bullet {
+                    /* cover the whole path of the last step */
+                    let v = b.p - b.pp;
+                    (b.p - v / 2.0, b.extent() + v.length() / 2.0)
+                } else {
+                    (b.p, b.extent())
+                }
+            }))
+            .collect()
+    }
+
+    fn is_bullet(&mut self, i: usize) -> bool {
+        self.body(i).map_or(false, |b| b.bullet)
+    }
+
+    /* when the bullet i hit j during the last step */
+    fn sweep_pair(&mut self, i: usize, j: usize) -> Option<f32> {
+        let world = self.world;
+        let (this, other) = self.pair(i, j);
+
+        match (this.body(), other.body()) {
+            (Some(a), Some(b)) if physics::can_collide(a, b) => physics::sweep(a, b, &world),
+            _ => None,
         }
     }
 
-    pub fn think(&mut self, dt: f32, input: &Input, hud: &mut Hud) {
-        for (i, e) in self.entities.iter_mut().enumerate() {
-            let state = e.think(dt, input, hud, &mut self.born);
-            if state == State::Dead {
-                self.dead.push(i);
+    fn collide_all(&mut self) {
+        let proxies = self.proxies();
+
+        /* bullets only hit the first thing on their path */
+        let mut swept = Vec::new();
+        for (i, j) in self.grid.pairs(&proxies) {
+            match (self.is_bullet(i), self.is_bullet(j)) {
+                (true, false) => if let Some(t) = self.sweep_pair(i, j) { swept.push((t, i, j)) },
+                (false, true) => if let Some(t) = self.sweep_pair(j, i) { swept.push((t, j, i)) },
+                _ => self.collide_pair(i, j),
             }
         }
 
-        while let Some(i) = self.dead.pop() {
-            self.entities.swap_remove(i);
+        /* a broken time can't be ordered, leave it out so the rest sort
+         * as a total order */
+        swept.retain(|s| s.0.is_finite());
+        swept.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut done = Vec::new();
+        for (t, bullet, other) in swept {
+            if done.contains(&bullet) {
+                continue;
+            }
+            done.push(bullet);
+
+            if let Some(b) = self.body(bullet) {
+                b.rewind(t);
+            }
+            let (i, j) = if bullet > other { (bullet, other) } else { (other, bullet) };
+            self.collide_pair(i, j);
         }
+    }
 
-        while let Some(e) = self.born.pop() {
-            self.entities.push(e);
+    /// Same as `collide_all`, testing every pair.
+    pub fn collide_all_brute_force(&mut self) {
+        let proxies = self.proxies();
+        for (i, j) in broadphase::brute_force(&proxies) {
+            self.collide_pair(i, j);
         }
+    }
+
+    fn check_damage(&mut self) {
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            let health = match slot.entity {
+                Some(ref e) => e.health(),
+                None => continue,
+            };
+            if let (Some(before), Some(now)) = (slot.health, health) {
+                if now < before {
+                    self.events.push(Event::DamageTaken {
+                        id: EntityId { index: i as u32, generation: slot.generation },
+                        amount: before - now,
+                        health: now,
+                        by: slot.hit_by.map(|h| h.0),
+                    });
+                }
+            }
+            slot.health = health;
+        }
+    }
+
+    /// Advance every entity by `dt`, with what each player slot did.
+    pub fn think(&mut self, dt: f32, inputs: &[Snapshot]) {
+        self.events.clear();
+        self.events.extend(self.added.drain(..));
+
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            if let Some(ref mut e) = slot.entity {
+                self.spawner.parent = Some(EntityId { index: i as u32, generation: slot.generation });
+                let state = e.think(dt, inputs, &self.world, &mut self.rng, &mut self.spawner);
+                if state == State::Dead {
+                    self.dead.push(i);
+                }
+            }
+        }
+
+        self.remove_dead();
+        self.add_born();
 
         self.collide_all();
+        self.check_damage();
+    }
+}
+
+#[test]
+fn test_headless() {
+    use std::rc::Rc;
+    use cgmath::vec2;
+    use ship::{ Ship, ShipMeta };
+    use roid::Generator;
+    use render::Null;
+
+    let mut engine = Engine::new(1, World::default());
+    let mut renderer = Null;
+    let input = [Snapshot::new()];
+
+    let still = Generator { speed: (0.0, 0.0), spin: (0.0, 0.0), ..Generator::new() };
+    let roid = still.create_at(engine.rng(), vec2(100.0, 0.0));
+    engine.add(Box::new(Ship::new(vec2(-100.0, 0.0), Rc::new(ShipMeta::default()))));
+    engine.add(Box::new(roid));
+
+    for _ in 0..5000 {
+        engine.think(1.0 / 60.0, &input);
+        engine.draw(&mut renderer, 1.0);
+    }
+
+    assert_eq!(engine.len(), 2);
+}
+
+#[test]
+fn test_seeded() {
+    use std::rc::Rc;
+    use cgmath::vec2;
+    use glutin::{ Event, ElementState, VirtualKeyCode };
+    use input::Input;
+    use ship::{ Ship, ShipMeta };
+    use roid::Generator;
+
+    fn run(seed: u32) -> Vec<(physics::V32, f32)> {
+        let mut engine = Engine::new(seed, World::default());
+        let mut input = Input::new();
+        input.handle_event(&Event::KeyboardInput(ElementState::Pressed, 0, Some(VirtualKeyCode::Space)));
+
+        let roid = Generator::new().create_at(engine.rng(), vec2(100.0, 0.0));
+        engine.add(Box::new(Ship::new(vec2(-100.0, 0.0), Rc::new(ShipMeta::default()))));
+        engine.add(Box::new(roid));
+
+        for _ in 0..1200 {
+            engine.think(1.0 / 60.0, &[input.tick(1.0 / 60.0).clone()]);
+        }
+
+        engine.iter_mut()
+            .filter_map(|(_, e)| e.body().map(|b| (b.p, b.a)))
+            .collect()
+    }
+
+    let a = run(42);
+    assert!(a.len() > 2);
+    assert!(a == run(42));
+    assert!(a != run(43));
+}
+
+#[test]
+fn test_ids() {
+    use boom::Boom;
+
+    /* lives for a number of ticks, maybe spawning another on the first one */
+    struct Dummy {
+        ticks: u32,
+        spawn: bool,
+        child: Option<EntityId>,
+    }
+
+    impl Entity for Dummy {
+        fn draw(&mut self, _: &mut Render, _: &World, _: f32) {}
+        fn think(&mut self, _: f32, _: &[Snapshot], _: &World, _: &mut Random, born: &mut Spawner) -> State {
+            if self.spawn {
+                self.spawn = false;
+                self.child = Some(born.push(Box::new(Dummy { ticks: 100, spawn: false, child: None })));
+            }
+            if self.ticks == 0 {
+                return State::Dead;
+            }
+            self.ticks -= 1;
+            State::Alive
+        }
+        fn collide(&mut self, _: &mut Entity, _: &Contact) {}
+        fn body(&mut self) -> Option<&mut physics::Body> { None }
+        fn kind(&self) -> Kind { Kind::Effect }
+    }
+
+    let mut engine = Engine::new(1, World::default());
+    let input = [Snapshot::new()];
+    let dummy = |ticks| Box::new(Dummy { ticks: ticks, spawn: false, child: None });
+
+    let a = engine.add(dummy(100));
+    let b = engine.add(dummy(0));
+    let c = engine.add(dummy(100));
+    let boom = Boom::new(engine.rng(), 0.0, 0.0, 0.0);
+    let boom = engine.add(Box::new(boom));
+    let parent = engine.add(Box::new(Dummy { ticks: 100, spawn: true, child: None }));
+
+    engine.think(1.0 / 60.0, &input);
+
+    /* b is gone, the others keep their ids */
+    assert!(!engine.contains(b));
+    assert!(engine.get(b).is_none());
+    assert_eq!(engine.get_as::<Dummy>(a).unwrap().ticks, 99);
+    assert_eq!(engine.get_as::<Dummy>(c).unwrap().ticks, 99);
+    assert!(engine.get_as::<Dummy>(boom).is_none());
+    assert!(engine.get_as::<Boom>(boom).is_some());
+
+    /* the spawned one is there under the id it was given */
+    let child = engine.get_as::<Dummy>(parent).unwrap().child.unwrap();
+    assert_eq!(engine.get_as::<Dummy>(child).unwrap().ticks, 100);
+
+    /* a new entity takes over b's slot, b's id stays stale */
+    let d = engine.add(dummy(100));
+    assert_eq!(d.index, b.index);
+    assert!(d != b);
+    assert!(engine.get(b).is_none());
+    assert!(engine.get(d).is_some());
+
+    assert_eq!(engine.iter_of::<Dummy>().count(), 5);
+    assert_eq!(engine.iter_of::<Boom>().count(), 1);
+    assert_eq!(engine.len(), 6);
+}
+
+#[test]
+fn test_events() {
+    use std::rc::Rc;
+    use cgmath::vec2;
+    use input::{ Key, Input };
+    use ship::{ Ship, ShipMeta };
+    use roid::Generator;
+
+    let mut engine = Engine::new(1, World::default());
+    let mut input = Input::new();
+    input.set(Key::Fire, true);
+
+    let still = Generator { speed: (0.0, 0.0), spin: (0.0, 0.0), ..Generator::new() };
+    let roid = still.create_at(engine.rng(), vec2(100.0, 0.0));
+    let ship = engine.add(Box::new(Ship::new(vec2(-100.0, 0.0), Rc::new(ShipMeta::default()))));
+    let roid = engine.add(Box::new(roid));
+
+    let mut events = Vec::new();
+    for _ in 0..60 {
+        engine.think(1.0 / 60.0, &[input.tick(1.0 / 60.0).clone()]);
+        events.extend(engine.events().iter().cloned());
+    }
+
+    let shots: Vec<EntityId> = events.iter().filter_map(|e| match *e {
+        Event::ShotFired { by, shot } if by == ship => Some(shot),
+        _ => None,
+    }).collect();
+    assert!(shots.len() > 1);
+
+    /* things added from outside have nobody to thank for it */
+    for &id in &[ship, roid] {
+        assert!(events.iter().any(|e| match *e {
+            Event::Spawned { id: spawned, by, .. } => spawned == id && by.is_none(),
+            _ => false,
+        }));
+    }
+
+    for e in events.iter() {
+        match *e {
+            Event::Spawned { id, kind: Kind::Projectile, by } => {
+                assert!(shots.contains(&id));
+                assert_eq!(by, Some(ship));
+            },
+            Event::Collision { a, b, energy, .. } => {
+                assert!(a == roid || b == roid);
+                assert!(shots.contains(&a) || shots.contains(&b));
+                assert!(energy >= 0.0);
+            },
+            _ => (),
+        }
+    }
+
+    /* the rock is hurt, and knows who did it */
+    assert!(events.iter().any(|e| match *e {
+        Event::DamageTaken { id, by, amount, .. } => id == roid && by == Some(ship) && amount > 0.0,
+        _ => false,
+    }));
+
+    /* shots die on the rock */
+    assert!(events.iter().any(|e| match *e {
+        Event::Destroyed { id, kind: Kind::Projectile, by, with, .. } =>
+            shots.contains(&id) && by == Some(roid) && with == Some(Kind::Asteroid),
+        _ => false,
+    }));
+
+    /* nothing happens without someone shooting */
+    input.set(Key::Fire, false);
+    for _ in 0..600 {
+        engine.think(1.0 / 60.0, &[input.tick(1.0 / 60.0).clone()]);
     }
+    assert!(engine.events().is_empty());
 }
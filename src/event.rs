@@ -0,0 +1,49 @@
+use entity::EntityId;
+use physics::V32;
+
+/// What an entity is, as far as anyone listening for events cares.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Kind {
+    Ship,
+    Asteroid,
+    Projectile,
+    Effect,
+}
+
+/// Things that happened during a tick, see `Engine::events`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Event {
+    Collision {
+        a: EntityId,
+        b: EntityId,
+        energy: f32,
+        point: V32,
+    },
+    /* `by` is who gets the credit: the owner of whatever hit it last,
+     * or that thing itself if nobody owns it, `with` is what that thing
+     * was, so that shooting can be told from ramming */
+    Destroyed {
+        id: EntityId,
+        kind: Kind,
+        by: Option<EntityId>,
+        with: Option<Kind>,
+        /* radius of its body, zero if it had none */
+        size: f32,
+    },
+    /* `by` is the entity that spawned it, if any */
+    Spawned {
+        id: EntityId,
+        kind: Kind,
+        by: Option<EntityId>,
+    },
+    DamageTaken {
+        id: EntityId,
+        amount: f32,
+        health: f32,
+        by: Option<EntityId>,
+    },
+    ShotFired {
+        by: EntityId,
+        shot: EntityId,
+    },
+}
@@ -0,0 +1,463 @@
+use std::rc::Rc;
+
+use cgmath::vec2;
+
+use entity::{ Engine, EntityId };
+use event::{ Event, Kind };
+use input::Snapshot;
+use hud::Hud;
+use physics::V32;
+use render::Render;
+use ship::{ Ship, ShipMeta };
+use director::{ Director, Wave };
+use world::World;
+
+/* spare ships at the start */
+const LIVES: u32 = 3;
+/* seconds before the next ship comes in, and how long it is shielded */
+const RESPAWN_DELAY: f32 = 2.0;
+const RESPAWN_SHIELD: f32 = 3.0;
+/* distance between the ships of two players when they come in */
+const SPAWN_SPACING: f32 = 60.0;
+/* points for shooting down another player, in versus */
+const KILL_POINTS: u32 = 500;
+
+/// Most players in one game.
+pub const MAX_PLAYERS: usize = 4;
+
+/// Points for destroying an asteroid, smaller ones are worth more.
+pub fn points(radius: f32) -> u32 {
+    ((2000.0 / radius.max(1.0) / 10.0).round() * 10.0) as u32
+}
+
+/// How players get along when there is more than one.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Mode {
+    /* everyone against the rocks, shots fly through the other ships */
+    Coop,
+    /* the same, but shots hit whichever ship is in the way */
+    FriendlyFire,
+    /* shooting another player down scores, the last one flying wins */
+    Versus,
+}
+
+pub const MODES: &'static [Mode] = &[
+    Mode::Coop,
+    Mode::FriendlyFire,
+    Mode::Versus,
+];
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Mode::Coop => "coop",
+            Mode::FriendlyFire => "friendly-fire",
+            Mode::Versus => "versus",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Mode> {
+        MODES.iter().find(|m| m.name() == name).map(|m| *m)
+    }
+}
+
+/* one player slot: its ship, and what it has left */
+struct Player {
+    ship: EntityId,
+    /* every ship flown so far, shots outlive the ship that fired them */
+    flown: Vec<EntityId>,
+    spawn: V32,
+    score: u32,
+    lives: u32,
+    /* time left until the next ship, while waiting for one */
+    respawn: Option<f32>,
+    /* lost the last ship */
+    out: bool,
+}
+
+/* a fresh ship for a player slot, following the rules of `mode` */
+fn new_ship(meta: &Rc<ShipMeta>, mode: Mode, slot: usize, spawn: V32) -> Ship {
+    let mut ship = Ship::new(spawn, meta.clone()).for_player(slot);
+    ship.set_friendly_fire(mode != Mode::Coop);
+    ship
+}
+
+/* player slot that flew `ship` */
+fn slot_of(players: &[Player], ship: EntityId) -> Option<usize> {
+    players.iter().position(|p| p.flown.contains(&ship))
+}
+
+/// One game session: the simulation and everything it reports to.
+pub struct Game {
+    pub engine: Engine,
+    pub hud: Hud,
+    pub director: Director,
+
+    players: Vec<Player>,
+    mode: Mode,
+    ship_meta: Rc<ShipMeta>,
+    /* where the players come in, kept clear of rocks */
+    spawn: V32,
+    over: bool,
+    /* the first player aims with the mouse */
+    turret: bool,
+}
+
+impl Game {
+    pub fn new(seed: u32, world: World, ship_meta: Rc<ShipMeta>, waves: Vec<Wave>) -> Game {
+        Game::with_players(seed, world, ship_meta, waves, 1, Mode::Coop)
+    }
+
+    /// A game for `players` at one screen, each flying with the input
+    /// of their slot.
+    pub fn with_players(seed: u32, world: World, ship_meta: Rc<ShipMeta>, waves: Vec<Wave>,
+                        players: usize, mode: Mode) -> Game {
+        let mut engine = Engine::new(seed, world);
+        let n = players.max(1).min(MAX_PLAYERS);
+        let spawn = vec2(-100.0, 0.0);
+        let mut hud = Hud::new(&world, n);
+
+        let mut slots = Vec::new();
+        for slot in 0..n {
+            let p = spawn + vec2(0.0, SPAWN_SPACING * (slot as f32 - (n - 1) as f32 / 2.0));
+            let ship = new_ship(&ship_meta, mode, slot, p);
+            let score = ship.score;
+            hud.set_score(slot, score, LIVES);
+            let ship = engine.add(Box::new(ship));
+            slots.push(Player {
+                ship: ship,
+                flown: vec![ship],
+                spawn: p,
+                score: score,
+                lives: LIVES,
+                respawn: None,
+                out: false,
+            });
+        }
+
+        let mut director = Director::new(waves);
+        director.start(&mut engine, &mut hud, spawn);
+
+        Game {
+            engine: engine,
+            hud: hud,
+            director: director,
+
+            players: slots,
+            mode: mode,
+            ship_meta: ship_meta,
+            spawn: spawn,
+            over: false,
+            turret: false,
+        }
+    }
+
+    pub fn players(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Current ship of a player, which may be gone.
+    pub fn player(&self, slot: usize) -> EntityId {
+        self.players[slot].ship
+    }
+
+    pub fn score(&self, slot: usize) -> u32 {
+        self.players[slot].score
+    }
+
+    /// Highest score of any player, the one that goes in the table.
+    pub fn best_score(&self) -> u32 {
+        self.players.iter().map(|p| p.score).max().unwrap_or(0)
+    }
+
+    /// Spare ships left.
+    pub fn lives(&self, slot: usize) -> u32 {
+        self.players[slot].lives
+    }
+
+    /// Whether the game is decided: every ship is gone, or in versus
+    /// only one player is left.
+    pub fn over(&self) -> bool {
+        self.over
+    }
+
+    /// Player still flying at the end of a versus game.
+    pub fn winner(&self) -> Option<usize> {
+        if self.over && self.mode == Mode::Versus && self.players.len() > 1 {
+            self.players.iter().position(|p| !p.out)
+        } else {
+            None
+        }
+    }
+
+    /// Give the first player's ships a mouse aimed turret, this one and
+    /// the ones after it.
+    pub fn set_turret(&mut self, on: bool) {
+        self.turret = on;
+        if let Some(ship) = self.engine.get_as::<Ship>(self.players[0].ship) {
+            ship.set_turret(on);
+        }
+    }
+
+    /// Advance one tick, with what each player slot did.
+    pub fn think(&mut self, dt: f32, inputs: &[Snapshot]) {
+        self.engine.think(dt, inputs);
+
+        let mut credit = Vec::new();
+        let mut died = Vec::new();
+        for event in self.engine.events() {
+            match *event {
+                /* only shots score, ramming doesn't */
+                Event::Destroyed { kind: Kind::Asteroid, by: Some(by), with: Some(Kind::Projectile), size, .. } => {
+                    if let Some(slot) = slot_of(&self.players, by) {
+                        credit.push((slot, points(size)));
+                    }
+                },
+                Event::Destroyed { id, kind: Kind::Ship, by, with, .. } => {
+                    let victim = slot_of(&self.players, id);
+                    if let Some(slot) = victim {
+                        died.push(slot);
+                    }
+                    match (by.and_then(|by| slot_of(&self.players, by)), with) {
+                        (Some(slot), Some(Kind::Projectile)) if Some(slot) != victim && self.mode == Mode::Versus =>
+                            credit.push((slot, KILL_POINTS)),
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        /* the score is kept on the ship while there is one */
+        for (slot, points) in credit {
+            let player = &mut self.players[slot];
+            match self.engine.get_as::<Ship>(player.ship) {
+                Some(ship) => ship.score += points,
+                None => player.score += points,
+            }
+        }
+
+        if !self.over {
+            let mut clear = self.spawn;
+            for player in self.players.iter() {
+                if let Some(p) = self.engine.get(player.ship).and_then(|e| e.body().map(|b| b.p)) {
+                    clear = p;
+                    break;
+                }
+            }
+            self.director.think(dt, &mut self.engine, &mut self.hud, clear);
+        }
+
+        for slot in died {
+            let player = &mut self.players[slot];
+            if player.lives > 0 {
+                player.lives -= 1;
+                player.respawn = Some(RESPAWN_DELAY);
+            } else {
+                player.out = true;
+            }
+        }
+
+        if !self.over {
+            let left = self.players.iter().filter(|p| !p.out).count();
+            let versus = self.mode == Mode::Versus && self.players.len() > 1;
+            if left == 0 || versus && left == 1 {
+                self.over = true;
+                let message = match self.winner() {
+                    Some(slot) => format!("PLAYER {} WINS", slot + 1),
+                    None => String::from("GAME OVER"),
+                };
+                self.hud.set_message(Some(&message));
+            }
+        }
+
+        for (slot, player) in self.players.iter_mut().enumerate() {
+            if let Some(t) = player.respawn {
+                if t > dt {
+                    player.respawn = Some(t - dt);
+                } else {
+                    player.respawn = None;
+                    let mut ship = new_ship(&self.ship_meta, self.mode, slot, player.spawn).shielded(RESPAWN_SHIELD);
+                    ship.score = player.score;
+                    ship.set_turret(self.turret && slot == 0);
+                    player.ship = self.engine.add(Box::new(ship));
+                    player.flown.push(player.ship);
+                }
+            }
+        }
+
+        for (slot, player) in self.players.iter_mut().enumerate() {
+            match self.engine.get_as::<Ship>(player.ship) {
+                Some(ship) => {
+                    player.score = ship.score;
+                    self.hud.update(slot, ship.energy_level(), ship.health_level());
+                },
+                None => self.hud.update(slot, 0.0, 0.0),
+            }
+            self.hud.set_score(slot, player.score, player.lives);
+        }
+    }
+
+    pub fn draw(&mut self, renderer: &mut Render, alpha: f32) {
+        self.engine.draw(renderer, alpha);
+        self.hud.draw(renderer);
+    }
+}
+
+#[test]
+fn test_score_and_lives() {
+    use input::{ Key, Input };
+    use roid::{ Generator, SizeClass };
+
+    let mut game = Game::new(5, World::default(), Rc::new(ShipMeta::default()), Vec::new());
+    let mut input = Input::new();
+    let dt = 1.0 / 60.0;
+
+    assert_eq!(points(50.0), 40);
+    assert!(points(10.0) > points(20.0));
+
+    /* put a small rock in front of the ship, and shoot until something breaks */
+    let small = Generator {
+        sizes: vec![ SizeClass { radius: (12.0, 12.0), weight: 1.0 } ],
+        speed: (0.0, 0.0),
+        ..Generator::new()
+    };
+    let roid = small.create_at(game.engine.rng(), vec2(-40.0, 0.0));
+    game.engine.add(Box::new(roid));
+
+    input.set(Key::Fire, true);
+    for _ in 0..3600 {
+        game.think(dt, &[input.tick(dt).clone()]);
+        if game.score(0) > 0 {
+            break;
+        }
+    }
+    assert!(game.score(0) > 0);
+    assert_eq!(game.hud.score(0), game.score(0));
+    let score = game.score(0);
+
+    /* lose every ship, and one more */
+    let input = [Snapshot::new()];
+    for life in 0..LIVES + 1 {
+        assert!(!game.over());
+        assert_eq!(game.lives(0), LIVES - life);
+
+        game.engine.get_as::<Ship>(game.player(0)).unwrap().health = 0.0;
+        game.think(dt, &input);
+        game.think(dt, &input);
+        assert!(game.engine.get(game.player(0)).is_none());
+
+        for _ in 0..(RESPAWN_DELAY / dt) as usize + 1 {
+            game.think(dt, &input);
+        }
+        if life < LIVES {
+            /* the score carries over, and the new ship starts out shielded */
+            let ship = game.engine.get_as::<Ship>(game.player(0)).unwrap();
+            assert!(ship.is_shielded());
+            assert_eq!(ship.score, score);
+        }
+    }
+
+    assert!(game.over());
+    assert_eq!(game.lives(0), 0);
+    assert_eq!(game.hud.message(), Some("GAME OVER"));
+    assert!(game.engine.get(game.player(0)).is_none());
+}
+
+#[test]
+fn test_credit() {
+    use input::{ Key, Input };
+    use roid::{ Generator, Material, SizeClass };
+
+    let dt = 1.0 / 60.0;
+    let idle = [Snapshot::new()];
+    let brittle = Generator {
+        sizes: vec![ SizeClass { radius: (12.0, 12.0), weight: 1.0 } ],
+        materials: vec![ (Material { toughness: 1e-6, ..Material::rock() }, 1.0) ],
+        speed: (0.0, 0.0),
+        ..Generator::new()
+    };
+
+    /* ramming a rock to pieces is worth nothing */
+    let mut game = Game::new(5, World::default(), Rc::new(ShipMeta::default()), Vec::new());
+    let p = game.engine.get_as::<Ship>(game.player(0)).unwrap().body.p;
+    let roid = brittle.create_at(game.engine.rng(), p + vec2(60.0, 0.0));
+    let roid = game.engine.add(Box::new(roid));
+    game.engine.get_as::<Ship>(game.player(0)).unwrap().body.dp = vec2(300.0, 0.0);
+    for _ in 0..30 {
+        game.think(dt, &idle);
+    }
+    assert!(game.engine.get(roid).is_none());
+    assert_eq!(game.score(0), 0);
+
+    /* shots still score once the ship that fired them is gone */
+    let mut game = Game::new(5, World::default(), Rc::new(ShipMeta::default()), Vec::new());
+    let roid = brittle.create_at(game.engine.rng(), p + vec2(100.0, 0.0));
+    let roid = game.engine.add(Box::new(roid));
+    let mut input = Input::new();
+    input.set(Key::Fire, true);
+    for _ in 0..5 {
+        game.think(dt, &[input.tick(dt).clone()]);
+    }
+    assert!(game.engine.get(roid).is_some());
+    game.engine.get_as::<Ship>(game.player(0)).unwrap().health = 0.0;
+    for _ in 0..60 {
+        game.think(dt, &idle);
+    }
+    assert!(game.engine.get(roid).is_none());
+    assert!(game.engine.get(game.player(0)).is_none());
+    assert!(game.score(0) > 0);
+}
+
+#[test]
+fn test_modes() {
+    use input::{ Key, Input };
+
+    /* the second player shoots at the first from behind, one hit is
+     * enough to finish it off */
+    fn duel(mode: Mode) -> Game {
+        let dt = 1.0 / 60.0;
+        let mut game = Game::with_players(5, World::default(), Rc::new(ShipMeta::default()), Vec::new(), 2, mode);
+        let p = game.engine.get_as::<Ship>(game.player(1)).unwrap().body.p;
+        let first = game.player(0);
+        let ship = game.engine.get_as::<Ship>(first).unwrap();
+        ship.body.p = p + vec2(60.0, 0.0);
+        ship.health = 0.01;
+
+        let mut input = Input::new();
+        input.set(Key::Fire, true);
+        for _ in 0..120 {
+            let inputs = [Snapshot::new(), input.tick(dt).clone()];
+            game.think(dt, &inputs);
+        }
+        game
+    }
+
+    let coop = duel(Mode::Coop);
+    assert_eq!(coop.lives(0), LIVES);
+
+    let friendly = duel(Mode::FriendlyFire);
+    assert!(friendly.lives(0) < LIVES);
+    assert_eq!(friendly.score(1), 0);
+
+    let mut versus = duel(Mode::Versus);
+    assert!(versus.lives(0) < LIVES);
+    assert!(versus.score(1) >= KILL_POINTS);
+
+    /* the last one flying wins */
+    let dt = 1.0 / 60.0;
+    let idle = [Snapshot::new(), Snapshot::new()];
+    while !versus.over() {
+        let first = versus.player(0);
+        if let Some(ship) = versus.engine.get_as::<Ship>(first) {
+            ship.health = 0.0;
+        }
+        versus.think(dt, &idle);
+    }
+    assert_eq!(versus.winner(), Some(1));
+    assert_eq!(versus.hud.message(), Some("PLAYER 2 WINS"));
+}
@@ -1,7 +1,8 @@
 use cgmath;
 use cgmath::vec2;
 
-use render;
+use render::{ self, text, Render, Vertex };
+use world::World;
 
 pub type V32 = cgmath::Vector2<f32>;
 
@@ -28,9 +29,11 @@ impl Bar {
         self.val = val;
     }
 
-    pub fn draw(&self, renderer: &mut render::Renderer) {
-        use render::Vertex;
+    pub fn get(&self) -> f32 {
+        self.val
+    }
 
+    pub fn draw(&self, renderer: &mut Render) {
         let i = self.pos;
         let j = self.pos + self.dim - self.dim * self.dir * (1.0 - self.val);
 
@@ -49,34 +52,134 @@ impl Bar {
     }
 }
 
-pub struct Hud {
+/* bars and counters of one player */
+struct Panel {
     energy: Bar,
     health: Bar,
+
+    score: u32,
+    lives: u32,
+
+    left: f32,
+    right: f32,
+}
+
+pub struct Hud {
+    panels: Vec<Panel>,
+    message: Option<String>,
+
+    hi: V32,
 }
 
+/* space between the panels of two players */
+const PANEL_GAP: f32 = 60.0;
+
 impl Hud {
-    pub fn new() -> Hud {
+    /// Panels for `players` side by side across the top of the world.
+    pub fn new(world: &World, players: usize) -> Hud {
+        let (lo, hi) = (world.lo(), world.hi());
+        let n = players.max(1) as f32;
+        let width = (hi.x - lo.x - 140.0 - PANEL_GAP * (n - 1.0)) / n;
+
+        let panels = (0..players.max(1)).map(|i| {
+            let left = lo.x + 70.0 + (width + PANEL_GAP) * i as f32;
+            Panel {
+                energy: Bar::new(
+                   vec2(left, hi.y - 110.0),
+                   vec2(width, 10.0),
+                   vec2(1.0, 0.0),
+                   [ 0.9, 0.6, 0.3, 1.0 ]),
+                health: Bar::new(
+                   vec2(left, hi.y - 90.0),
+                   vec2(width, 20.0),
+                   vec2(1.0, 0.0),
+                   [ 0.3, 0.6, 0.9, 1.0 ]),
+
+                score: 0,
+                lives: 0,
+
+                left: left,
+                right: left + width,
+            }
+        }).collect();
+
         Hud {
-            energy: Bar::new(
-               vec2(-280.0, 240.0),
-               vec2(560.0, 10.0),
-               vec2(1.0, 0.0),
-               [ 0.9, 0.6, 0.3, 1.0 ]),
-            health: Bar::new(
-               vec2(-280.0, 260.0),
-               vec2(560.0, 20.0),
-               vec2(1.0, 0.0),
-               [ 0.3, 0.6, 0.9, 1.0 ]),
+            panels: panels,
+            message: None,
+
+            hi: hi,
         }
     }
 
-    pub fn update(&mut self, energy: f32, health: f32) {
-        self.energy.set(energy);
-        self.health.set(health);
+    pub fn players(&self) -> usize {
+        self.panels.len()
     }
 
-    pub fn draw(&mut self, renderer: &mut render::Renderer) {
-        self.energy.draw(renderer);
-        self.health.draw(renderer);
+    pub fn update(&mut self, player: usize, energy: f32, health: f32) {
+        self.panels[player].energy.set(energy);
+        self.panels[player].health.set(health);
+    }
+
+    pub fn energy(&self, player: usize) -> f32 {
+        self.panels[player].energy.get()
+    }
+
+    pub fn health(&self, player: usize) -> f32 {
+        self.panels[player].health.get()
+    }
+
+    /// Score and spare ships left of a player.
+    pub fn set_score(&mut self, player: usize, score: u32, lives: u32) {
+        self.panels[player].score = score;
+        self.panels[player].lives = lives;
+    }
+
+    pub fn score(&self, player: usize) -> u32 {
+        self.panels[player].score
+    }
+
+    pub fn lives(&self, player: usize) -> u32 {
+        self.panels[player].lives
+    }
+
+    /// Text shown across the middle of the screen, until replaced.
+    pub fn set_message(&mut self, message: Option<&str>) {
+        self.message = message.map(|m| m.to_owned());
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_ref().map(|m| &m[..])
+    }
+
+    pub fn draw(&mut self, renderer: &mut Render) {
+        let color = [ 0.9, 0.9, 0.9, 1.0 ];
+        let top = self.hi.y - 60.0;
+
+        for (i, panel) in self.panels.iter().enumerate() {
+            let (left, right) = (panel.left, panel.right);
+
+            panel.energy.draw(renderer);
+            panel.health.draw(renderer);
+
+            text::draw(renderer, &panel.score.to_string(), left, top, 20.0, color);
+
+            /* the ship icon tells whose panel it is */
+            let lives = panel.lives.to_string();
+            text::draw(renderer, &lives, right - text::width(&lives, 20.0), top, 20.0, color);
+            let icon = right - text::width(&lives, 20.0) - 20.0;
+            let mut ship = renderer.create_shape(render::ShapeType::Game, render::player_color(i), &[
+                Vertex::new([0.0, 0.0]),
+                Vertex::new([6.0, 20.0]),
+                Vertex::new([12.0, 0.0]),
+                Vertex::new([6.0, 5.0]),
+                Vertex::new([0.0, 0.0]),
+            ]);
+            ship.set_transform(icon, top, 0.0);
+            renderer.draw_shape(&mut ship);
+        }
+
+        if let Some(ref message) = self.message {
+            text::draw_centered(renderer, message, 0.0, 0.0, 30.0, color);
+        }
     }
 }
@@ -0,0 +1,253 @@
+//! Polygon outlines for the narrowphase.
+//!
+//! Shapes are given the way they are drawn, as polar vertex lists around
+//! the body centre. Such outlines are star shaped, so they split into a fan
+//! of convex pieces around the centre, which are then tested pairwise with
+//! the separating axis test.
+
+use std::f32;
+
+use cgmath::{ vec2, rad, Vector, EuclideanVector, Matrix2 };
+
+use physics::V32;
+use render::from_polar;
+
+pub struct Hull {
+    /* convex pieces, counter-clockwise, in body coordinates */
+    parts: Vec<Vec<V32>>,
+    radius: f32,
+}
+
+fn cross(a: V32, b: V32) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/* turns left going a -> b -> c */
+fn left(a: V32, b: V32, c: V32) -> bool {
+    cross(b - a, c - b) > 0.0
+}
+
+impl Hull {
+    /// Hull of an outline in the format taken by `create_shape_simple`.
+    pub fn from_polar(outline: &[[f32; 2]]) -> Hull {
+        let mut points: Vec<V32> = outline.iter()
+            .map(|p| { let p = from_polar(p); vec2(p[0], p[1]) })
+            .collect();
+
+        /* outlines repeat the first vertex to close the loop */
+        if points.len() > 1 && points[0] == points[points.len() - 1] {
+            points.pop();
+        }
+
+        let radius = points.iter().fold(0.0, |r: f32, p| r.max(p.length()));
+        let o = vec2(0.0, 0.0);
+        let n = points.len();
+
+        if n >= 3 && (0..n).all(|i| left(points[i], points[(i + 1) % n], points[(i + 2) % n])) {
+            return Hull {
+                parts: vec![points],
+                radius: radius,
+            };
+        }
+
+        /* grow each piece of the fan for as long as it stays convex */
+        let mut parts = Vec::new();
+        let mut i = 0;
+        while i < n {
+            let (first, second) = (points[i], points[(i + 1) % n]);
+            if !left(o, first, second) {
+                /* the centre is not inside this bit of the outline */
+                i += 1;
+                continue;
+            }
+
+            let mut part = vec![o, first, second];
+            let mut j = i + 1;
+            while j + 1 < n || (j + 1 == n && i > 0) {
+                let next = points[(j + 1) % n];
+                let last = part[part.len() - 1];
+                let prev = part[part.len() - 2];
+                if !(left(prev, last, next) && left(last, next, o) && left(next, o, first)) {
+                    break;
+                }
+                part.push(next);
+                j += 1;
+            }
+
+            parts.push(part);
+            i = j;
+        }
+
+        Hull {
+            parts: parts,
+            radius: radius,
+        }
+    }
+
+    /// Distance from the centre to the furthest vertex.
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    /// Pieces placed at `p`, turned by `a` (in units of π).
+    pub fn place(&self, p: V32, a: f32) -> Vec<Convex> {
+        let m = Matrix2::from_angle(rad(a * f32::consts::PI));
+        self.parts.iter()
+            .map(|part| Convex::polygon(part.iter().map(|&v| p + m * v).collect()))
+            .collect()
+    }
+}
+
+/// A convex polygon or a circle, in world coordinates.
+pub struct Convex {
+    points: Vec<V32>,
+    radius: f32,
+    /* bounding circle */
+    centre: V32,
+    bound: f32,
+}
+
+impl Convex {
+    pub fn polygon(points: Vec<V32>) -> Convex {
+        let centre = points.iter().fold(vec2(0.0, 0.0), |sum, &p| sum + p) / points.len() as f32;
+        let bound = points.iter().fold(0.0, |r: f32, &p| r.max((p - centre).length()));
+        Convex {
+            points: points,
+            radius: 0.0,
+            centre: centre,
+            bound: bound,
+        }
+    }
+
+    pub fn circle(p: V32, r: f32) -> Convex {
+        Convex {
+            points: vec![p],
+            radius: r,
+            centre: p,
+            bound: r,
+        }
+    }
+
+    fn project(&self, n: V32) -> (f32, f32) {
+        let (lo, hi) = self.points.iter()
+            .map(|p| p.dot(n))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), d| (lo.min(d), hi.max(d)));
+        (lo - self.radius, hi + self.radius)
+    }
+
+    /* the point furthest along n */
+    fn support(&self, n: V32) -> V32 {
+        let mut best = self.points[0];
+        for &p in self.points[1..].iter() {
+            if p.dot(n) > best.dot(n) {
+                best = p;
+            }
+        }
+        best + n * self.radius
+    }
+
+    fn edge_normals(&self, axes: &mut Vec<V32>) {
+        let n = self.points.len();
+        if n < 2 {
+            return;
+        }
+        for i in 0..n {
+            let e = self.points[(i + 1) % n] - self.points[i];
+            if e.length2() > 0.0 {
+                axes.push(vec2(e.y, -e.x).normalize());
+            }
+        }
+    }
+}
+
+/// Where two convex shapes overlap: depth, normal pointing from `b`
+/// towards `a`, and contact point.
+pub fn sat(a: &Convex, b: &Convex) -> Option<(f32, V32, V32)> {
+    if (a.centre - b.centre).length() >= a.bound + b.bound {
+        return None;
+    }
+
+    let mut axes = Vec::new();
+    a.edge_normals(&mut axes);
+    b.edge_normals(&mut axes);
+
+    /* rounded shapes can also be separated along the line between vertices */
+    if a.radius > 0.0 || b.radius > 0.0 {
+        for &pa in a.points.iter() {
+            for &pb in b.points.iter() {
+                let d = pa - pb;
+                if d.length2() > 0.0 {
+                    axes.push(d.normalize());
+                }
+            }
+        }
+    }
+
+    let mut best: Option<(f32, V32)> = None;
+    for n in axes {
+        let (alo, ahi) = a.project(n);
+        let (blo, bhi) = b.project(n);
+        let (depth, n) = if ahi - blo < bhi - alo { (ahi - blo, -n) } else { (bhi - alo, n) };
+
+        if depth <= 0.0 {
+            return None;
+        }
+        if best.map_or(true, |(d, _)| depth < d) {
+            best = Some((depth, n));
+        }
+    }
+
+    best.map(|(depth, n)| {
+        let point = (a.support(-n) + b.support(n)) / 2.0;
+        (depth, n, point)
+    })
+}
+
+/// Deepest overlap between any pieces of two shapes, same as `sat`.
+pub fn overlap(a: &[Convex], b: &[Convex]) -> Option<(f32, V32, V32)> {
+    let mut best: Option<(f32, V32, V32)> = None;
+    for pa in a {
+        for pb in b {
+            if let Some(hit) = sat(pa, pb) {
+                if best.map_or(true, |b| hit.0 > b.0) {
+                    best = Some(hit);
+                }
+            }
+        }
+    }
+    best
+}
+
+#[test]
+fn test_hull() {
+    /* a square, corners at radius 10 */
+    let square = Hull::from_polar(&[[0.25, 10.0], [0.75, 10.0], [1.25, 10.0], [1.75, 10.0], [0.25, 10.0]]);
+    assert_eq!(square.len(), 1);
+    assert!((square.radius() - 10.0).abs() < 1e-4);
+
+    /* overlapping side by side, pushed apart along x */
+    let a = square.place(vec2(0.0, 0.0), 0.0);
+    let b = square.place(vec2(12.0, 1.0), 0.0);
+    let (depth, n, p) = overlap(&a, &b).unwrap();
+    assert!((depth - (2.0 * 7.0711 - 12.0)).abs() < 1e-3);
+    assert!((n - vec2(-1.0, 0.0)).length() < 1e-4);
+    assert!((p.x - 6.0).abs() < 1.0);
+
+    /* bounding circles overlap, but turned 45° the corners miss */
+    let b = square.place(vec2(16.0, 0.0), 0.25);
+    assert!(overlap(&a, &b).is_some());
+    let b = square.place(vec2(16.0, 8.0), 0.0);
+    assert!(overlap(&a, &b).is_none());
+
+    /* a circle in the notch of a concave shape touches nothing */
+    let notched = Hull::from_polar(&[[0.0, 10.0], [0.4, 10.0], [0.5, 2.0], [0.6, 10.0], [1.0, 10.0], [1.5, 10.0], [0.0, 10.0]]);
+    assert!(notched.len() > 1);
+    let parts = notched.place(vec2(0.0, 0.0), 0.0);
+    assert!(overlap(&parts, &[Convex::circle(vec2(0.0, 8.0), 2.0)]).is_none());
+    assert!(overlap(&parts, &[Convex::circle(vec2(0.0, 1.0), 2.0)]).is_some());
+    assert!(overlap(&parts, &[Convex::circle(vec2(8.0, 0.0), 2.0)]).is_some());
+}
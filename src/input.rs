@@ -1,53 +1,639 @@
-use std::collections::HashMap;
-use glutin::{ Event, ElementState, VirtualKeyCode };
+use std::cell::RefCell;
+use std::collections::{ HashMap, HashSet };
+use std::rc::Rc;
+use cgmath::vec2;
+use glutin::{ Event, ElementState, MouseButton, VirtualKeyCode };
+use keymap::{ self, Binding, Keymap };
+use physics::V32;
+use render::Viewport;
 
-#[derive(Copy, Clone, Hash, PartialEq, Eq)]
+#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
 pub enum Key {
     Left,
     Right,
     Forward,
     Reverse,
     Fire,
+    /* menus and game flow */
+    Start,
+    Pause,
+    Quit,
+}
+
+/* order matters, index of a key is its bit in `Input::bits` */
+pub const KEYS: &'static [Key] = &[
+    Key::Left,
+    Key::Right,
+    Key::Forward,
+    Key::Reverse,
+    Key::Fire,
+    Key::Start,
+    Key::Pause,
+    Key::Quit,
+];
+
+impl Key {
+    /// Name of the action in key binding files.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Key::Left => "left",
+            Key::Right => "right",
+            Key::Forward => "forward",
+            Key::Reverse => "reverse",
+            Key::Fire => "fire",
+            Key::Start => "start",
+            Key::Pause => "pause",
+            Key::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Key> {
+        KEYS.iter().find(|k| k.name() == name).cloned()
+    }
+}
+
+/// Bit of a key in `Input::bits` and `Snapshot::bits`.
+pub fn bit(key: Key) -> u32 {
+    1 << KEYS.iter().position(|k| *k == key).unwrap()
+}
+
+/// Actions that take any value in a range, rather than on or off.
+#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
+pub enum Axis {
+    /* -1 is full right, 1 full left */
+    Turn,
+    /* 0 to 1, forward only */
+    Thrust,
+}
+
+/* order matters, index of an axis is its place in `Snapshot::analog` */
+pub const AXES: &'static [Axis] = &[
+    Axis::Turn,
+    Axis::Thrust,
+];
+
+impl Axis {
+    pub fn range(&self) -> (f32, f32) {
+        match *self {
+            Axis::Turn => (-1.0, 1.0),
+            Axis::Thrust => (0.0, 1.0),
+        }
+    }
+
+    fn index(&self) -> usize {
+        AXES.iter().position(|a| a == self).unwrap()
+    }
+}
+
+/* analog values are kept in steps of 1/127, so replays get them exactly */
+fn quantize(v: f32) -> i8 {
+    (v.max(-1.0).min(1.0) * 127.0).round() as i8
+}
+
+/* stick positions this close to the middle read as 0 */
+const DEADZONE: f32 = 0.15;
+
+/* raw device position with the deadzone cut out, still going all the
+ * way to 1 at the edge */
+fn filter(v: f32, deadzone: f32) -> f32 {
+    if v.abs() <= deadzone {
+        0.0
+    } else {
+        v.signum() * (v.abs().min(1.0) - deadzone) / (1.0 - deadzone)
+    }
+}
+
+/// Input from something other than the keyboard, like a gamepad.
+pub trait Device {
+    /// Actions held, as bits of `KEYS`.
+    fn buttons(&mut self) -> u32;
+    /// Raw position of an axis from -1 to 1, the deadzone still in it.
+    fn axis(&mut self, axis: Axis) -> f32;
+}
+
+/// Device moved by hand, for tests and scripted input. Clones share
+/// their state, so one can be kept to move the one given to `Input`.
+#[derive(Clone, Default)]
+pub struct Fake {
+    state: Rc<RefCell<(u32, [f32; 2])>>,
+}
+
+impl Fake {
+    pub fn new() -> Fake {
+        Fake::default()
+    }
+
+    pub fn set(&self, key: Key, pressed: bool) {
+        let buttons = &mut self.state.borrow_mut().0;
+        if pressed {
+            *buttons |= bit(key);
+        } else {
+            *buttons &= !bit(key);
+        }
+    }
+
+    pub fn set_axis(&self, axis: Axis, v: f32) {
+        self.state.borrow_mut().1[axis.index()] = v;
+    }
+}
+
+impl Device for Fake {
+    fn buttons(&mut self) -> u32 {
+        self.state.borrow().0
+    }
+
+    fn axis(&mut self, axis: Axis) -> f32 {
+        self.state.borrow().1[axis.index()]
+    }
+}
+
+/// What the keys and axes did during one tick.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Snapshot {
+    bits: u32,
+    pressed: u32,
+    released: u32,
+    /* pressed again without being seen up in between, part of `pressed` */
+    again: u32,
+    /* seconds each key has been down, in the order of KEYS */
+    held: Vec<f32>,
+    /* axes from devices, keys come on top of these */
+    analog: [i8; 2],
+    /* world position the mouse points at, in whole units */
+    aim: Option<[i16; 2]>,
+}
+
+impl Snapshot {
+    /// Nothing held, nothing happening.
+    pub fn new() -> Snapshot {
+        Snapshot {
+            bits: 0,
+            pressed: 0,
+            released: 0,
+            again: 0,
+            held: vec![0.0; KEYS.len()],
+            analog: [0; 2],
+            aim: None,
+        }
+    }
+
+    /// The tick after this one, with `bits` down and `again` pressed a
+    /// second time while they were held, axes at `analog` and the mouse
+    /// at `aim`.
+    pub fn next(&self, bits: u32, again: u32, analog: [i8; 2], aim: Option<[i16; 2]>, dt: f32) -> Snapshot {
+        let again = again & bits & self.bits;
+        let held = self.held.iter().enumerate()
+            .map(|(i, t)| if bits & 1 << i == 0 { 0.0 } else if again & 1 << i != 0 { dt } else { t + dt })
+            .collect();
+
+        Snapshot {
+            bits: bits,
+            pressed: bits & !self.bits | again,
+            released: self.bits & !bits,
+            again: again,
+            held: held,
+            analog: analog,
+            aim: aim,
+        }
+    }
+
+    /// Keys down during the tick, packed as in `Input::bits`.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn again(&self) -> u32 {
+        self.again
+    }
+
+    /// Axes from devices, in steps of 1/127 and in the order of AXES.
+    pub fn analog(&self) -> [i8; 2] {
+        self.analog
+    }
+
+    /// Mouse position as packed in the snapshot.
+    pub fn aim_raw(&self) -> Option<[i16; 2]> {
+        self.aim
+    }
+
+    /// Point in the world the mouse is on, if it is in the window.
+    pub fn aim(&self) -> Option<V32> {
+        self.aim.map(|a| vec2(a[0] as f32, a[1] as f32))
+    }
+
+    /// Where an axis is, keys that move it count as all the way.
+    pub fn axis(&self, axis: Axis) -> f32 {
+        let key = |k| if self.pressed(k) { 1.0 } else { 0.0 };
+        let keys = match axis {
+            Axis::Turn => key(Key::Left) - key(Key::Right),
+            Axis::Thrust => key(Key::Forward),
+        };
+        let (lo, hi) = axis.range();
+        (keys + self.analog[axis.index()] as f32 / 127.0).max(lo).min(hi)
+    }
+
+    pub fn pressed(&self, key: Key) -> bool {
+        self.bits & bit(key) != 0
+    }
+
+    /// Whether the key went down since the last tick.
+    pub fn just_pressed(&self, key: Key) -> bool {
+        self.pressed & bit(key) != 0
+    }
+
+    /// Whether the key came up since the last tick.
+    pub fn just_released(&self, key: Key) -> bool {
+        self.released & bit(key) != 0
+    }
+
+    /// Seconds the key has been down, counting this tick.
+    pub fn held_for(&self, key: Key) -> f32 {
+        self.held[KEYS.iter().position(|k| *k == key).unwrap()]
+    }
 }
 
 pub struct Input {
     state: HashMap<Key, bool>,
-    keymap: HashMap<VirtualKeyCode, Key>,
+    keymap: Keymap,
+
+    /* keys held, and the action each of them set off */
+    held: HashSet<VirtualKeyCode>,
+    active: HashMap<VirtualKeyCode, Key>,
+    /* last key pressed, for rebinding; a modifier on its own counts
+     * only once it is let go without another key in between */
+    last: Option<Binding>,
+    lone_modifier: Option<VirtualKeyCode>,
+
+    /* keys that went down since the last tick, so a press and release
+     * between two ticks still counts, and the tick they are seen in */
+    presses: u32,
+    snapshot: Snapshot,
+
+    devices: Vec<Box<Device>>,
+    deadzone: f32,
+    /* axes set directly, on top of the devices */
+    axes: [f32; 2],
+
+    /* mouse in the world, and whether its button fires */
+    viewport: Option<Viewport>,
+    aim: Option<[i16; 2]>,
+    mouse_fire: bool,
 }
 
 impl Input {
     pub fn new() -> Input {
-        let mut input = Input {
+        Input::with_keymap(Keymap::default())
+    }
+
+    pub fn with_keymap(keymap: Keymap) -> Input {
+        Input {
             state: HashMap::new(),
-            keymap: HashMap::new(),
-        };
+            keymap: keymap,
+
+            held: HashSet::new(),
+            active: HashMap::new(),
+            last: None,
+            lone_modifier: None,
+
+            presses: 0,
+            snapshot: Snapshot::new(),
+
+            devices: Vec::new(),
+            deadzone: DEADZONE,
+            axes: [0.0; 2],
+
+            viewport: None,
+            aim: None,
+            mouse_fire: false,
+        }
+    }
+
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
 
-        input.keymap.insert(VirtualKeyCode::Left, Key::Left);
-        input.keymap.insert(VirtualKeyCode::Right, Key::Right);
-        input.keymap.insert(VirtualKeyCode::Up, Key::Forward);
-        input.keymap.insert(VirtualKeyCode::Down, Key::Reverse);
-        input.keymap.insert(VirtualKeyCode::Space, Key::Fire);
+    pub fn keymap_mut(&mut self) -> &mut Keymap {
+        &mut self.keymap
+    }
+
+    pub fn add_device(&mut self, device: Box<Device>) {
+        self.devices.push(device);
+    }
+
+    /// How far from the middle device axes have to move to count.
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone.max(0.0).min(0.99);
+    }
+
+    /// Put an axis somewhere, as if a device without deadzone held it.
+    pub fn set_axis(&mut self, axis: Axis, v: f32) {
+        self.axes[axis.index()] = v;
+    }
+
+    /// Set the axes from `Snapshot::analog`, so the next tick sees the same.
+    pub fn set_analog(&mut self, analog: [i8; 2]) {
+        for axis in AXES {
+            self.set_axis(*axis, analog[axis.index()] as f32 / 127.0);
+        }
+    }
 
-        input
+    /// Window the mouse moves in, needed to aim with it. With several
+    /// players only the one given a window follows the mouse.
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = Some(viewport);
+    }
+
+    /// Point the mouse at a world position, as `Snapshot::aim_raw` has it.
+    pub fn set_aim(&mut self, aim: Option<[i16; 2]>) {
+        self.aim = aim;
+    }
+
+    /// The key pressed since the last call, with the modifiers held.
+    pub fn take_binding(&mut self) -> Option<Binding> {
+        self.last.take()
     }
 
     pub fn pressed(&self, key: Key) -> bool {
         *self.state.get(&key).unwrap_or(&false)
     }
 
+    pub fn set(&mut self, key: Key, pressed: bool) {
+        if pressed && !self.pressed(key) {
+            self.presses |= bit(key);
+        }
+        self.state.insert(key, pressed);
+    }
+
+    /// Move on to the next tick: everything pressed since the last one
+    /// is seen as down, even if it is already up again.
+    pub fn tick(&mut self, dt: f32) -> &Snapshot {
+        let mut bits = self.bits() | self.presses;
+        let mut analog = [0; 2];
+        for axis in AXES {
+            let mut v = self.axes[axis.index()];
+            for device in self.devices.iter_mut() {
+                v += filter(device.axis(*axis), self.deadzone);
+            }
+            analog[axis.index()] = quantize(v);
+        }
+        for device in self.devices.iter_mut() {
+            bits |= device.buttons();
+        }
+        if self.mouse_fire {
+            bits |= bit(Key::Fire);
+        }
+
+        self.snapshot = self.snapshot.next(bits, self.presses, analog, self.aim, dt);
+        self.presses = 0;
+        &self.snapshot
+    }
+
+    /// What the keys did in the last tick.
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// State of all keys packed into a bit set.
+    pub fn bits(&self) -> u32 {
+        KEYS.iter()
+            .enumerate()
+            .filter(|&(_, key)| self.pressed(*key))
+            .fold(0, |bits, (i, _)| bits | 1 << i)
+    }
+
+    /// Set every key, as `bits` and `again` from a snapshot, so that the
+    /// next tick sees the same.
+    pub fn set_bits(&mut self, bits: u32, again: u32) {
+        for (i, key) in KEYS.iter().enumerate() {
+            self.set(*key, bits & 1 << i != 0);
+        }
+        self.presses = bits & !self.snapshot.bits | again;
+    }
+
     fn handle_key(&mut self, el_state: &ElementState, keycode: &VirtualKeyCode) {
-        let new_state = *el_state == ElementState::Pressed;
+        let code = *keycode;
+        let modifier = keymap::modifier(code);
+
+        if *el_state == ElementState::Pressed {
+            /* ignore key repeat */
+            if !self.held.insert(code) {
+                return;
+            }
+            let mods = self.mods(code);
+
+            if modifier != 0 {
+                self.lone_modifier = Some(code);
+            } else {
+                self.lone_modifier = None;
+                self.last = Some(Binding { code: code, mods: mods });
+            }
 
-        if let Some(key) = self.keymap.get(keycode) {
-            let the_state = self.state.entry(*key).or_insert(false);
-            *the_state = new_state;
+            if let Some(key) = self.keymap.lookup(code, mods) {
+                self.active.insert(code, key);
+                self.set(key, true);
+            }
+        } else {
+            self.held.remove(&code);
+            if modifier != 0 && self.lone_modifier == Some(code) {
+                self.lone_modifier = None;
+                self.last = Some(Binding { code: code, mods: self.mods(code) });
+            }
+
+            /* the action stays on while another of its keys is held */
+            if let Some(key) = self.active.remove(&code) {
+                let held = self.active.values().any(|k| *k == key);
+                self.set(key, held);
+            }
         }
     }
 
+    /* modifiers held besides `code` */
+    fn mods(&self, code: VirtualKeyCode) -> u8 {
+        self.held.iter()
+            .filter(|c| **c != code)
+            .fold(0, |mods, c| mods | keymap::modifier(*c))
+    }
+
     pub fn handle_event(&mut self, ev: &Event) {
         match *ev {
             Event::KeyboardInput(ref state, _, Some(ref key)) => self.handle_key(state, key),
+            Event::MouseMoved((x, y)) => {
+                if let Some(viewport) = self.viewport {
+                    /* inside the world, so it fits the i16s, see world::MAX_SIZE */
+                    let p = viewport.to_world(x as f32, y as f32);
+                    self.aim = Some([p.x.round() as i16, p.y.round() as i16]);
+                }
+            }
+            Event::MouseInput(ref state, MouseButton::Left) if self.viewport.is_some() => {
+                let pressed = *state == ElementState::Pressed;
+                if pressed && !self.mouse_fire && !self.pressed(Key::Fire) {
+                    self.presses |= bit(Key::Fire);
+                }
+                self.mouse_fire = pressed;
+            }
             _ => (),
         }
     }
 }
+
+#[test]
+fn test_bindings() {
+    use glutin::VirtualKeyCode as Code;
+
+    fn key(input: &mut Input, code: Code, pressed: bool) {
+        let state = if pressed { ElementState::Pressed } else { ElementState::Released };
+        input.handle_event(&Event::KeyboardInput(state, 0, Some(code)));
+    }
+
+    let mut keymap = Keymap::default();
+    keymap.bind(Key::Fire, Binding::new(Code::LControl));
+    keymap.bind(Key::Left, Binding::new(Code::A));
+    keymap.bind(Key::Pause, Binding { code: Code::A, mods: keymap::SHIFT });
+    let mut input = Input::with_keymap(keymap);
+
+    /* two keys for one action, it stays on until both are up */
+    key(&mut input, Code::A, true);
+    key(&mut input, Code::Left, true);
+    key(&mut input, Code::A, false);
+    assert!(input.pressed(Key::Left));
+    key(&mut input, Code::Left, false);
+    assert!(!input.pressed(Key::Left));
+
+    /* a modifier picks a different action, and can be an action itself */
+    key(&mut input, Code::LShift, true);
+    key(&mut input, Code::A, true);
+    assert!(input.pressed(Key::Pause) && !input.pressed(Key::Left));
+    key(&mut input, Code::LShift, false);
+    key(&mut input, Code::A, false);
+    assert!(!input.pressed(Key::Pause));
+    assert_eq!(input.take_binding(), Some(Binding { code: Code::A, mods: keymap::SHIFT }));
+
+    key(&mut input, Code::LControl, true);
+    assert!(input.pressed(Key::Fire));
+    key(&mut input, Code::LControl, false);
+    assert_eq!(input.take_binding(), Some(Binding::new(Code::LControl)));
+    assert_eq!(input.take_binding(), None);
+
+    /* both players see every key, the left and right control keys are
+     * told apart so each can hold fire at once */
+    let mut first = Keymap::default();
+    first.bind(Key::Fire, Binding::new(Code::RControl));
+    let mut second = Keymap::for_player(1);
+    second.bind(Key::Fire, Binding::new(Code::LControl));
+    let mut inputs = vec![Input::with_keymap(first), Input::with_keymap(second)];
+    for code in &[Code::LControl, Code::RControl] {
+        for input in inputs.iter_mut() {
+            key(input, *code, true);
+        }
+    }
+    assert!(inputs[0].pressed(Key::Fire) && inputs[1].pressed(Key::Fire));
+    for input in inputs.iter_mut() {
+        key(input, Code::LControl, false);
+    }
+    assert!(inputs[0].pressed(Key::Fire) && !inputs[1].pressed(Key::Fire));
+}
+
+#[test]
+fn test_edges() {
+    let dt = 0.25;
+    let mut input = Input::new();
+
+    input.set(Key::Fire, true);
+    let s = input.tick(dt).clone();
+    assert!(s.pressed(Key::Fire) && s.just_pressed(Key::Fire));
+    assert_eq!(s.held_for(Key::Fire), 0.25);
+
+    let s = input.tick(dt).clone();
+    assert!(s.pressed(Key::Fire) && !s.just_pressed(Key::Fire));
+    assert_eq!(s.held_for(Key::Fire), 0.5);
+
+    input.set(Key::Fire, false);
+    let s = input.tick(dt).clone();
+    assert!(!s.pressed(Key::Fire) && s.just_released(Key::Fire));
+    assert_eq!(s.held_for(Key::Fire), 0.0);
+
+    /* a tap between two ticks still shows up, for one tick */
+    input.set(Key::Start, true);
+    input.set(Key::Start, false);
+    let s = input.tick(dt).clone();
+    assert!(s.just_pressed(Key::Start));
+    let s = input.tick(dt).clone();
+    assert!(s.just_released(Key::Start) && !s.pressed(Key::Start));
+
+    /* so does letting go and pressing again, and the hold starts over */
+    input.set(Key::Left, true);
+    input.tick(dt);
+    input.set(Key::Left, false);
+    input.set(Key::Left, true);
+    let s = input.tick(dt).clone();
+    assert!(s.just_pressed(Key::Left));
+    assert_eq!(s.held_for(Key::Left), 0.25);
+    assert!(!input.tick(dt).just_pressed(Key::Left));
+}
+
+#[test]
+fn test_device() {
+    let dt = 1.0 / 60.0;
+    let mut input = Input::new();
+    let pad = Fake::new();
+    input.add_device(Box::new(pad.clone()));
+    input.set_deadzone(0.2);
+
+    /* inside the deadzone nothing happens, past it the range starts over */
+    pad.set_axis(Axis::Turn, 0.1);
+    assert_eq!(input.tick(dt).axis(Axis::Turn), 0.0);
+    pad.set_axis(Axis::Turn, -0.6);
+    assert_eq!(input.tick(dt).analog()[0], quantize(-0.5));
+    pad.set_axis(Axis::Turn, 1.0);
+    assert_eq!(input.tick(dt).axis(Axis::Turn), 1.0);
+
+    /* keys count as full, and nothing goes past the ends */
+    pad.set_axis(Axis::Turn, 0.6);
+    input.set(Key::Right, true);
+    assert!((input.tick(dt).axis(Axis::Turn) + 0.5).abs() < 0.01);
+    input.set(Key::Left, true);
+    input.set(Key::Right, false);
+    assert_eq!(input.tick(dt).axis(Axis::Turn), 1.0);
+
+    pad.set_axis(Axis::Thrust, -1.0);
+    assert_eq!(input.tick(dt).axis(Axis::Thrust), 0.0);
+
+    /* device buttons work like keys */
+    pad.set(Key::Fire, true);
+    assert!(input.tick(dt).just_pressed(Key::Fire));
+    pad.set(Key::Fire, false);
+    assert!(input.tick(dt).just_released(Key::Fire));
+}
+
+#[test]
+fn test_mouse() {
+    use world::{self, World};
+
+    let dt = 1.0 / 60.0;
+    let mut input = Input::new();
+
+    /* without a window there is nothing to point at or click */
+    input.handle_event(&Event::MouseMoved((10, 10)));
+    input.handle_event(&Event::MouseInput(ElementState::Pressed, MouseButton::Left));
+    assert_eq!(input.tick(dt).aim(), None);
+    assert!(!input.tick(dt).pressed(Key::Fire));
+    input.handle_event(&Event::MouseInput(ElementState::Released, MouseButton::Left));
+
+    input.set_viewport(Viewport::new(&World::new(800.0, 600.0), 400.0, 300.0));
+    input.handle_event(&Event::MouseMoved((300, 75)));
+    assert_eq!(input.tick(dt).aim(), Some(vec2(200.0, 150.0)));
+
+    /* far off the window stays on the edge of even the largest world */
+    let big = World::new(world::MAX_SIZE, world::MAX_SIZE);
+    input.set_viewport(Viewport::new(&big, 400.0, 300.0));
+    input.handle_event(&Event::MouseMoved((-100000, 100000)));
+    let edge = world::MAX_SIZE / 2.0;
+    assert_eq!(input.tick(dt).aim(), Some(vec2(-edge, -edge)));
+    input.set_viewport(Viewport::new(&World::new(800.0, 600.0), 400.0, 300.0));
+
+    /* the button fires, and a click between ticks still counts */
+    input.handle_event(&Event::MouseInput(ElementState::Pressed, MouseButton::Left));
+    input.handle_event(&Event::MouseInput(ElementState::Released, MouseButton::Left));
+    assert!(input.tick(dt).just_pressed(Key::Fire));
+    assert!(input.tick(dt).just_released(Key::Fire));
+}
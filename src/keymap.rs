@@ -0,0 +1,368 @@
+//! Which keyboard keys trigger which actions.
+//!
+//! Bindings are written as key names with optional modifiers in front,
+//! as in `"Ctrl+Space"`, and every action can have any number of them.
+//! Each player has a keymap of their own, read from `[keys]` for the
+//! first one and `[keys2]`, `[keys3]` and so on for the others.
+
+use std::fs::File;
+use std::io::{ self, Write };
+use std::path::Path;
+
+use glutin::VirtualKeyCode as Code;
+use input::{ Key, KEYS };
+use config::{ self, Section };
+
+/* modifier bits */
+pub const SHIFT: u8 = 1;
+pub const CTRL: u8 = 2;
+pub const ALT: u8 = 4;
+
+const MODIFIERS: &'static [(&'static str, u8)] = &[
+    ("Shift", SHIFT),
+    ("Ctrl", CTRL),
+    ("Alt", ALT),
+];
+
+/* actions the menus can't do without, they always keep a key */
+const MENU_KEYS: &'static [Key] = &[Key::Start, Key::Quit];
+
+/// Modifier bit of a modifier key, 0 for any other key.
+pub fn modifier(code: Code) -> u8 {
+    match code {
+        Code::LShift | Code::RShift => SHIFT,
+        Code::LControl | Code::RControl => CTRL,
+        Code::LAlt | Code::RAlt => ALT,
+        _ => 0,
+    }
+}
+
+/// A key, and the modifiers that have to be held with it.
+#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
+pub struct Binding {
+    pub code: Code,
+    pub mods: u8,
+}
+
+impl Binding {
+    pub fn new(code: Code) -> Binding {
+        Binding { code: code, mods: 0 }
+    }
+
+    pub fn parse(text: &str) -> Option<Binding> {
+        let mut parts: Vec<&str> = text.split('+').map(|p| p.trim()).collect();
+        let code = match parts.pop().and_then(code_from_name) {
+            Some(code) => code,
+            None => return None,
+        };
+
+        let mut mods = 0;
+        for part in parts {
+            match MODIFIERS.iter().find(|m| same_name(m.0, part)) {
+                Some(m) => mods |= m.1,
+                None => return None,
+            }
+        }
+        Some(Binding { code: code, mods: mods })
+    }
+
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        for &(m, bit) in MODIFIERS {
+            if self.mods & bit != 0 {
+                name.push_str(m);
+                name.push('+');
+            }
+        }
+        name.push_str(code_name(self.code));
+        name
+    }
+}
+
+/// Bindings of every action.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Keymap {
+    bindings: Vec<(Binding, Key)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        let mut keymap = Keymap { bindings: Vec::new() };
+        keymap.bind(Key::Left, Binding::new(Code::Left));
+        keymap.bind(Key::Right, Binding::new(Code::Right));
+        keymap.bind(Key::Forward, Binding::new(Code::Up));
+        keymap.bind(Key::Reverse, Binding::new(Code::Down));
+        keymap.bind(Key::Fire, Binding::new(Code::Space));
+        keymap.bind(Key::Start, Binding::new(Code::Return));
+        keymap.bind(Key::Pause, Binding::new(Code::P));
+        keymap.bind(Key::Quit, Binding::new(Code::Escape));
+        keymap
+    }
+}
+
+/// Name of the config section with the keys of a player slot.
+pub fn section_name(slot: usize) -> String {
+    if slot == 0 {
+        String::from("keys")
+    } else {
+        format!("keys{}", slot + 1)
+    }
+}
+
+impl Keymap {
+    /// Default keys of a player slot, so that players sharing a
+    /// keyboard stay out of each other's way. Past the fourth there
+    /// are no keys left and every action starts out unbound.
+    pub fn for_player(slot: usize) -> Keymap {
+        let keys: &[(Key, Code)] = match slot {
+            0 => return Keymap::default(),
+            1 => &[(Key::Left, Code::A), (Key::Right, Code::D), (Key::Forward, Code::W),
+                   (Key::Reverse, Code::S), (Key::Fire, Code::Tab)],
+            2 => &[(Key::Left, Code::J), (Key::Right, Code::L), (Key::Forward, Code::I),
+                   (Key::Reverse, Code::K), (Key::Fire, Code::Slash)],
+            3 => &[(Key::Left, Code::Numpad4), (Key::Right, Code::Numpad6), (Key::Forward, Code::Numpad8),
+                   (Key::Reverse, Code::Numpad5), (Key::Fire, Code::Numpad0)],
+            _ => &[],
+        };
+
+        let mut keymap = Keymap { bindings: Vec::new() };
+        for &(key, code) in keys {
+            keymap.bind(key, Binding::new(code));
+        }
+        keymap
+    }
+
+    /// Make `binding` trigger `key`, and nothing else. Refuses and
+    /// returns false when it is the last key of Start or Quit.
+    pub fn bind(&mut self, key: Key, binding: Binding) -> bool {
+        if let Some(&(_, old)) = self.bindings.iter().find(|b| b.0 == binding) {
+            if old != key && MENU_KEYS.contains(&old) && self.bindings_of(old).len() == 1 {
+                return false;
+            }
+        }
+        self.set(key, binding);
+        true
+    }
+
+    fn set(&mut self, key: Key, binding: Binding) {
+        self.bindings.retain(|b| b.0 != binding);
+        self.bindings.push((binding, key));
+    }
+
+    /// Drop the oldest binding of `key`, as long as it has another one.
+    pub fn unbind_oldest(&mut self, key: Key) {
+        if self.bindings_of(key).len() > 1 {
+            let i = self.bindings.iter().position(|b| b.1 == key).unwrap();
+            self.bindings.remove(i);
+        }
+    }
+
+    pub fn bindings_of(&self, key: Key) -> Vec<Binding> {
+        self.bindings.iter().filter(|b| b.1 == key).map(|b| b.0).collect()
+    }
+
+    /// Action for a key pressed while `mods` are held. When several
+    /// bindings match, the one asking for the most modifiers wins.
+    pub fn lookup(&self, code: Code, mods: u8) -> Option<Key> {
+        self.bindings.iter()
+            .filter(|b| b.0.code == code && b.0.mods & !mods == 0)
+            .max_by_key(|b| b.0.mods.count_ones())
+            .map(|b| b.1)
+    }
+
+    /// Read bindings from a `[keys]` section, actions not mentioned keep
+    /// their default keys.
+    pub fn from_section(section: &Section) -> Result<Keymap, config::Error> {
+        Keymap::default().read_section(section)
+    }
+
+    /* bindings of a section on top of these ones */
+    fn read_section(self, section: &Section) -> Result<Keymap, config::Error> {
+        let mut keymap = self;
+        let names: Vec<_> = KEYS.iter().map(|k| k.name()).collect();
+        let menu: Vec<Key> = MENU_KEYS.iter().cloned().filter(|&k| !keymap.bindings_of(k).is_empty()).collect();
+
+        for entry in section.entries.iter() {
+            let key = match Key::from_name(&entry.key) {
+                Some(key) => key,
+                None => return Err(entry.unknown(&section.name, &names)),
+            };
+
+            let texts = match entry.value {
+                config::Value::List(ref items) => items.clone(),
+                ref value => vec![value.clone()],
+            };
+
+            keymap.bindings.retain(|b| b.1 != key);
+            for text in texts {
+                let text = match text {
+                    config::Value::Str(text) => text,
+                    _ => return Err(entry.error(format!("`{}` should be a key name or a list of them", entry.key))),
+                };
+                match Binding::parse(&text) {
+                    Some(binding) => keymap.set(key, binding),
+                    None => return Err(entry.error(format!("unknown key `{}` for `{}`", text, entry.key))),
+                }
+            }
+        }
+
+        /* a file can't take the keys of the menus away either */
+        for key in menu {
+            if keymap.bindings_of(key).is_empty() {
+                return Err(config::Error::new(section.line, format!("[{}] leaves `{}` without a key", section.name, key.name())));
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Keymap, config::Error> {
+        Ok(try!(Keymap::load_players(path, 1)).remove(0))
+    }
+
+    /// Keymaps of the first `count` player slots, the ones without a
+    /// section keep their defaults.
+    pub fn load_players<P: AsRef<Path>>(path: P, count: usize) -> Result<Vec<Keymap>, config::Error> {
+        Keymap::from_sections(&try!(config::load(path)), count)
+    }
+
+    pub fn from_sections(sections: &[Section], count: usize) -> Result<Vec<Keymap>, config::Error> {
+        let mut keymaps = Vec::new();
+        for slot in 0..count {
+            let keymap = Keymap::for_player(slot);
+            keymaps.push(match sections.iter().find(|s| s.name == section_name(slot)) {
+                Some(section) => try!(keymap.read_section(section)),
+                None => keymap,
+            });
+        }
+        Ok(keymaps)
+    }
+
+    /// The bindings in the format `load` reads.
+    pub fn to_config(&self) -> String {
+        self.to_section(0)
+    }
+
+    fn to_section(&self, slot: usize) -> String {
+        let mut text = format!("[{}]\n", section_name(slot));
+        for key in KEYS {
+            let names: Vec<String> = self.bindings_of(*key).iter()
+                .map(|b| format!("\"{}\"", b.name()))
+                .collect();
+            text.push_str(&format!("{} = [ {} ]\n", key.name(), names.join(", ")));
+        }
+        text
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        Keymap::save_players(path, &[self.clone()])
+    }
+
+    /// Write the keymaps of several players, one section each.
+    pub fn save_players<P: AsRef<Path>>(path: P, keymaps: &[Keymap]) -> io::Result<()> {
+        let sections: Vec<String> = keymaps.iter().enumerate().map(|(slot, k)| k.to_section(slot)).collect();
+        let mut file = try!(File::create(path));
+        file.write_all(sections.join("\n").as_bytes())
+    }
+}
+
+const CODE_NAMES: &'static [(&'static str, Code)] = &[
+    ("A", Code::A), ("B", Code::B), ("C", Code::C), ("D", Code::D), ("E", Code::E),
+    ("F", Code::F), ("G", Code::G), ("H", Code::H), ("I", Code::I), ("J", Code::J),
+    ("K", Code::K), ("L", Code::L), ("M", Code::M), ("N", Code::N), ("O", Code::O),
+    ("P", Code::P), ("Q", Code::Q), ("R", Code::R), ("S", Code::S), ("T", Code::T),
+    ("U", Code::U), ("V", Code::V), ("W", Code::W), ("X", Code::X), ("Y", Code::Y),
+    ("Z", Code::Z),
+    ("0", Code::Key0), ("1", Code::Key1), ("2", Code::Key2), ("3", Code::Key3), ("4", Code::Key4),
+    ("5", Code::Key5), ("6", Code::Key6), ("7", Code::Key7), ("8", Code::Key8), ("9", Code::Key9),
+    ("F1", Code::F1), ("F2", Code::F2), ("F3", Code::F3), ("F4", Code::F4),
+    ("F5", Code::F5), ("F6", Code::F6), ("F7", Code::F7), ("F8", Code::F8),
+    ("F9", Code::F9), ("F10", Code::F10), ("F11", Code::F11), ("F12", Code::F12),
+    ("Left", Code::Left), ("Right", Code::Right), ("Up", Code::Up), ("Down", Code::Down),
+    ("Space", Code::Space), ("Return", Code::Return), ("Escape", Code::Escape),
+    ("Tab", Code::Tab), ("Back", Code::Back),
+    ("Insert", Code::Insert), ("Delete", Code::Delete), ("Home", Code::Home), ("End", Code::End),
+    ("PageUp", Code::PageUp), ("PageDown", Code::PageDown),
+    ("Numpad0", Code::Numpad0), ("Numpad1", Code::Numpad1), ("Numpad2", Code::Numpad2),
+    ("Numpad3", Code::Numpad3), ("Numpad4", Code::Numpad4), ("Numpad5", Code::Numpad5),
+    ("Numpad6", Code::Numpad6), ("Numpad7", Code::Numpad7), ("Numpad8", Code::Numpad8),
+    ("Numpad9", Code::Numpad9), ("NumpadEnter", Code::NumpadEnter),
+    ("Comma", Code::Comma), ("Period", Code::Period), ("Slash", Code::Slash),
+    ("Semicolon", Code::Semicolon), ("Apostrophe", Code::Apostrophe), ("Grave", Code::Grave),
+    ("Minus", Code::Minus), ("Equals", Code::Equals), ("Backslash", Code::Backslash),
+    ("LBracket", Code::LBracket), ("RBracket", Code::RBracket),
+    ("LShift", Code::LShift), ("RShift", Code::RShift),
+    ("LControl", Code::LControl), ("RControl", Code::RControl),
+    ("LAlt", Code::LAlt), ("RAlt", Code::RAlt),
+];
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+pub fn code_from_name(name: &str) -> Option<Code> {
+    CODE_NAMES.iter().find(|c| same_name(c.0, name)).map(|c| c.1)
+}
+
+/// Name of a key, `?` for the ones that can't be bound.
+pub fn code_name(code: Code) -> &'static str {
+    CODE_NAMES.iter().find(|c| c.1 == code).map_or("?", |c| c.0)
+}
+
+#[test]
+fn test_keymap() {
+    assert_eq!(Binding::parse("ctrl+Space"), Some(Binding { code: Code::Space, mods: CTRL }));
+    assert_eq!(Binding::parse("Shift+Alt+F1").unwrap().name(), "Shift+Alt+F1");
+    assert_eq!(Binding::parse("Hyper+A"), None);
+
+    let sections = config::parse(r#"
+        [keys]
+        left = [ "A", "Left" ]
+        fire = [ "Space", "Ctrl+Space" ]
+        quit = "Q"
+    "#).unwrap();
+    let keymap = Keymap::from_section(&sections[0]).unwrap();
+
+    assert_eq!(keymap.lookup(Code::A, 0), Some(Key::Left));
+    assert_eq!(keymap.lookup(Code::Left, SHIFT), Some(Key::Left));
+    assert_eq!(keymap.lookup(Code::Escape, 0), None);
+    assert_eq!(keymap.lookup(Code::Q, 0), Some(Key::Quit));
+    assert_eq!(keymap.lookup(Code::Up, 0), Some(Key::Forward));
+
+    /* binding a key takes it away from whatever had it before */
+    let mut other = keymap.clone();
+    other.bind(Key::Fire, Binding::new(Code::A));
+    assert_eq!(other.lookup(Code::A, 0), Some(Key::Fire));
+    other.unbind_oldest(Key::Left);
+    assert_eq!(other.bindings_of(Key::Left), vec![Binding::new(Code::Left)]);
+
+    /* and the last binding of an action stays */
+    other.unbind_oldest(Key::Left);
+    assert_eq!(other.bindings_of(Key::Left).len(), 1);
+
+    /* the menus always keep a key to get in and out */
+    assert!(!other.bind(Key::Fire, Binding::new(Code::Q)));
+    assert_eq!(other.lookup(Code::Q, 0), Some(Key::Quit));
+    assert!(other.bind(Key::Quit, Binding::new(Code::Escape)));
+    assert!(other.bind(Key::Fire, Binding::new(Code::Q)));
+    assert_eq!(other.bindings_of(Key::Quit), vec![Binding::new(Code::Escape)]);
+
+    let again = config::parse(&keymap.to_config()).unwrap();
+    assert_eq!(Keymap::from_section(&again[0]).unwrap().to_config(), keymap.to_config());
+
+    /* the other players start out on keys of their own */
+    let sections = config::parse("[keys2]\nfire = \"Q\"\n[other]\n").unwrap();
+    let players = Keymap::from_sections(&sections, 3).unwrap();
+    assert_eq!(players[0], Keymap::default());
+    assert_eq!(players[1].lookup(Code::Q, 0), Some(Key::Fire));
+    assert_eq!(players[1].lookup(Code::W, 0), Some(Key::Forward));
+    assert_eq!(players[2].lookup(Code::Up, 0), None);
+    let text = |keymaps: &[Keymap]| -> Vec<String> { keymaps.iter().enumerate().map(|(i, k)| k.to_section(i)).collect() };
+    let again = Keymap::from_sections(&config::parse(&text(&players).join("\n")).unwrap(), 3).unwrap();
+    assert_eq!(text(&again), text(&players));
+
+    let err = |text| Keymap::from_section(&config::parse(text).unwrap()[0]).err().unwrap().to_string();
+    assert_eq!(err("[keys]\nleft = \"Foo\""), "line 2: unknown key `Foo` for `left`");
+    assert_eq!(err("[keys]\nfire = \"Return\""), "line 1: [keys] leaves `start` without a key");
+    assert_eq!(err("[keys]\nfoward = \"W\""), "line 2: unknown field `foward` in [keys], did you mean `forward`?");
+}
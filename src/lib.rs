@@ -0,0 +1,44 @@
+#![feature(non_ascii_idents)]
+
+extern crate cgmath;
+#[macro_use]
+extern crate gfx;
+extern crate glutin;
+extern crate gfx_window_glutin;
+extern crate gfx_device_gl;
+extern crate time;
+extern crate rand;
+
+pub mod timer;
+pub mod config;
+pub mod input;
+pub mod keymap;
+pub mod world;
+pub mod physics;
+pub mod hull;
+pub mod broadphase;
+pub mod entity;
+pub mod event;
+pub mod ship;
+pub mod roid;
+pub mod render;
+pub mod hud;
+pub mod beam;
+pub mod boom;
+pub mod dust;
+pub mod director;
+pub mod game;
+pub mod app;
+pub mod replay;
+
+pub use entity::{ Engine, Entity, EntityId, State };
+pub use event::{ Event, Kind };
+pub use physics::Body;
+pub use world::World;
+pub use ship::{ Ship, ShipMeta };
+pub use roid::Roid;
+pub use beam::Beam;
+pub use boom::Boom;
+pub use dust::Dust;
+pub use hud::Hud;
+pub use render::Render;
@@ -1,65 +1,288 @@
-#![feature(non_ascii_idents)]
-
-extern crate cgmath;
-#[macro_use]
-extern crate gfx;
+extern crate roids;
 extern crate glutin;
-extern crate gfx_window_glutin;
-extern crate gfx_device_gl;
 extern crate time;
-extern crate rand;
-
-mod timer;
-mod input;
-mod physics;
-mod entity;
-mod ship;
-mod roid;
-mod render;
-mod hud;
-mod beam;
-mod boom;
 
+use std::env;
 use std::rc::Rc;
-use render::Renderer;
+use std::path::Path;
+use roids::{ input, timer };
+use roids::app::{ App, Screen };
+use roids::game::{ self, Mode };
+use roids::replay::{ Header, Recorder, Replay };
+use roids::render::Render;
+use roids::render::gl::Renderer;
+use roids::world::{ self, World };
+use roids::ship::ShipMeta;
+use roids::director::Wave;
+use roids::keymap::Keymap;
+use roids::config;
 use glutin::Event;
-use entity::Entity;
-use cgmath::vec2;
+
+/* ticks per second, unless --tick-rate says otherwise */
+const TICK_RATE: u32 = 60;
+/* key bindings are read from and saved back to here */
+const KEYS_FILE: &'static str = "keys.toml";
+
+struct Options {
+    seed: u32,
+    tick_rate: u32,
+    world: World,
+    record: Option<String>,
+    replay: Option<String>,
+    ships: Option<String>,
+    ship: Option<String>,
+    waves: Option<String>,
+    keys: String,
+    turret: bool,
+    players: usize,
+    mode: Mode,
+}
+
+impl Options {
+    fn parse() -> Options {
+        let mut opts = Options {
+            seed: time::precise_time_ns() as u32,
+            tick_rate: TICK_RATE,
+            world: World::default(),
+            record: None,
+            replay: None,
+            ships: None,
+            ship: None,
+            waves: None,
+            keys: KEYS_FILE.to_owned(),
+            turret: false,
+            players: 1,
+            mode: Mode::Coop,
+        };
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "--seed" => {
+                    opts.seed = args.next()
+                        .and_then(|v| v.parse().ok())
+                        .expect("--seed takes a number");
+                }
+                "--tick-rate" => {
+                    opts.tick_rate = args.next()
+                        .and_then(|v| v.parse().ok())
+                        .expect("--tick-rate takes a number");
+                    if opts.tick_rate < 1 || opts.tick_rate > timer::MAX_TICK_RATE {
+                        panic!("--tick-rate takes 1 to {}", timer::MAX_TICK_RATE);
+                    }
+                }
+                "--world" => {
+                    let size = args.next().expect("--world takes WIDTHxHEIGHT");
+                    let dims: Vec<f32> = size.split('x')
+                        .map(|v| v.parse().expect("--world takes WIDTHxHEIGHT"))
+                        .collect();
+                    if dims.len() != 2 {
+                        panic!("--world takes WIDTHxHEIGHT");
+                    }
+                    opts.world.width = dims[0];
+                    opts.world.height = dims[1];
+                    if !opts.world.is_valid() {
+                        panic!("--world takes sides from {} to {}", world::MIN_SIZE, world::MAX_SIZE);
+                    }
+                }
+                "--walls" => {
+                    opts.world = opts.world.walled();
+                }
+                "--record" => {
+                    opts.record = Some(args.next().expect("--record takes a file name"));
+                }
+                "--replay" => {
+                    opts.replay = Some(args.next().expect("--replay takes a file name"));
+                }
+                "--ships" => {
+                    opts.ships = Some(args.next().expect("--ships takes a file name"));
+                }
+                "--ship" => {
+                    opts.ship = Some(args.next().expect("--ship takes a class name"));
+                }
+                "--keys" => {
+                    opts.keys = args.next().expect("--keys takes a file name");
+                }
+                "--turret" => {
+                    opts.turret = true;
+                }
+                "--players" => {
+                    opts.players = args.next()
+                        .and_then(|v| v.parse().ok())
+                        .expect("--players takes a number");
+                    if opts.players < 1 || opts.players > game::MAX_PLAYERS {
+                        panic!("--players takes 1 to {}", game::MAX_PLAYERS);
+                    }
+                }
+                "--mode" => {
+                    let names: Vec<_> = game::MODES.iter().map(|m| m.name()).collect();
+                    opts.mode = args.next()
+                        .and_then(|v| Mode::from_name(&v))
+                        .unwrap_or_else(|| panic!("--mode takes one of {}", names.join(", ")));
+                }
+                "--waves" => {
+                    opts.waves = Some(args.next().expect("--waves takes a file name"));
+                }
+                _ => panic!("unknown argument: {}", arg),
+            }
+        }
+
+        opts
+    }
+}
+
+fn ship_meta(opts: &Options) -> ShipMeta {
+    let path = match opts.ships {
+        Some(ref path) => path,
+        None => {
+            if opts.ship.is_some() {
+                panic!("--ship needs a --ships file to pick from");
+            }
+            return ShipMeta::default();
+        }
+    };
+
+    let classes = config::load(path)
+        .and_then(|sections| ShipMeta::load_classes(&sections))
+        .unwrap_or_else(|e| panic!("{}: {}", path, e));
+
+    let pick = match opts.ship {
+        Some(ref name) => classes.into_iter().find(|c| c.0 == *name),
+        None => classes.into_iter().next(),
+    };
+
+    match pick {
+        Some((_, meta)) => meta,
+        None => panic!("{}: no ship class {}", path, opts.ship.as_ref().map_or("", |s| &s[..])),
+    }
+}
+
+fn waves(opts: &Options) -> Vec<Wave> {
+    match opts.waves {
+        Some(ref path) => config::load(path)
+            .and_then(|sections| Wave::load_waves(&sections))
+            .unwrap_or_else(|e| panic!("{}: {}", path, e)),
+        None => Vec::new(),
+    }
+}
+
+/* keys of every player slot, so the ones not playing are saved back too */
+fn keymaps(opts: &Options) -> Vec<Keymap> {
+    if !Path::new(&opts.keys).exists() {
+        return (0..game::MAX_PLAYERS).map(Keymap::for_player).collect();
+    }
+    Keymap::load_players(&opts.keys, game::MAX_PLAYERS).unwrap_or_else(|e| panic!("{}: {}", opts.keys, e))
+}
 
 fn main() {
-    let mut renderer = Renderer::new();
-    let mut input = input::Input::new();
-    let mut engine = entity::Engine::new();
-    let mut timer = timer::Timer::new(30.0, 61.0);
+    let opts = Options::parse();
+    let ship_meta = Rc::new(ship_meta(&opts));
+    let waves = waves(&opts);
+    let mut keymaps = keymaps(&opts);
 
-    let ship_meta = Rc::new(ship::ShipMeta::default());
-    let ship = ship::Ship::new(vec2(-100.0, 0.0), ship_meta.clone());
+    let mut replay = opts.replay.as_ref().map(|path| {
+        Replay::load(path).unwrap_or_else(|e| panic!("can't load replay {}: {}", path, e))
+    });
 
-    let rgen = roid::Generator::new();
-    let roid = rgen.create_at(vec2(100.0, 0.0));
+    /* a replay plays out the way it was recorded, whatever the options say */
+    let header = match replay {
+        Some(ref replay) => replay.header(),
+        None => Header {
+            seed: opts.seed,
+            tick_rate: opts.tick_rate,
+            world: opts.world,
+            players: opts.players,
+            mode: opts.mode,
+            turret: opts.turret,
+            ships: ship_meta.digest(),
+            waves: Wave::digest_waves(&waves),
+        },
+    };
+    let Header { seed, tick_rate, world, players, mode, turret, .. } = header;
 
-    engine.add(Box::new(ship));
-    engine.add(Box::new(roid));
+    /* the ship class and waves can't be stored, but playing with other
+     * ones would quietly go wrong */
+    if header.ships != ship_meta.digest() {
+        panic!("the replay was recorded with another ship class, give it the same --ships and --ship");
+    }
+    if header.waves != Wave::digest_waves(&waves) {
+        panic!("the replay was recorded with other waves, give it the same --waves");
+    }
+
+    let mut recorder = opts.record.as_ref().map(|_| Recorder::new(header));
 
-    let mut hud = hud::Hud::new();
+    let mut renderer = Renderer::new(&world);
+    let mut inputs: Vec<_> = (0..players)
+        .map(|slot| input::Input::with_keymap(keymaps.get(slot).cloned().unwrap_or_else(|| Keymap::for_player(slot))))
+        .collect();
+    let mut timer = timer::Timer::new(tick_rate as f64, 5);
+    let mut app = App::new(seed, world, ship_meta, waves);
+    app.set_turret(turret);
+    app.set_players(players, mode);
+    app.set_replaying(replay.is_some());
+    /* the mouse belongs to the first player */
+    inputs[0].set_viewport(renderer.viewport());
 
-    timer.are_we_yet();
+    timer.reset();
 
     'main: loop {
         for ev in renderer.get_window().poll_events() {
-            if let Event::Closed = ev {
+            match ev {
+                Event::Closed => break 'main,
+                Event::Resized(w, h) => {
+                    renderer.resize(w, h);
+                    inputs[0].set_viewport(renderer.viewport());
+                }
+                _ => (),
+            }
+            if replay.is_none() {
+                for input in inputs.iter_mut() {
+                    input.handle_event(&ev);
+                }
+            }
+        }
+
+        for _ in 0..timer.ticks() {
+            if let Some(replay) = replay.as_mut() {
+                if !replay.next(&mut inputs) {
+                    break 'main;
+                }
+            }
+            /* keys pressed on the controls screen aren't in the
+             * snapshots, replays skip it instead */
+            let controls = app.screen() == Screen::Controls;
+            if !app.think(timer.step() as f32, &mut inputs) {
                 break 'main;
             }
-            input.handle_event(&ev);
+
+            if let Some(recorder) = recorder.as_mut() {
+                let snapshots: Vec<_> = inputs.iter().map(|i| i.snapshot().clone()).collect();
+                if controls {
+                    recorder.skip(&snapshots);
+                } else {
+                    recorder.record(&snapshots);
+                }
+            }
         }
 
-        if let Some(dt) = timer.are_we_yet() {
-            engine.think(dt as f32, &input, &mut hud);
+        renderer.clear();
+        app.draw(&mut renderer, &inputs, timer.alpha());
+        renderer.finish();
+    }
+
+    if let (Some(recorder), Some(path)) = (recorder, opts.record.as_ref()) {
+        recorder.save(&path).unwrap_or_else(|e| panic!("can't save replay {}: {}", path, e));
+    }
 
-            renderer.clear();
-            engine.draw(&mut renderer);
-            hud.draw(&mut renderer);
-            renderer.finish();
+    let mut changed = false;
+    for (keymap, input) in keymaps.iter_mut().zip(inputs.iter()) {
+        if keymap != input.keymap() {
+            *keymap = input.keymap().clone();
+            changed = true;
         }
     }
+    if changed {
+        Keymap::save_players(&opts.keys, &keymaps)
+            .unwrap_or_else(|e| panic!("can't save key bindings {}: {}", opts.keys, e));
+    }
 }
@@ -1,7 +1,12 @@
 use std::f32::consts::PI;
+use std::rc::Rc;
 
 use cgmath::{ vec2, rad, Vector, EuclideanVector };
 
+use entity::EntityId;
+use hull::{ self, Hull, Convex };
+use world::World;
+
 pub type V32 = ::cgmath::Vector2<f32>;
 pub type M32 = ::cgmath::Matrix2<f32>;
 
@@ -13,15 +18,102 @@ pub struct Body {
     pub m: f32,
     pub dp: V32,
     pub da: f32,
+    /* friction coefficient at contacts */
+    pub μ: f32,
+
+    /* position and angle at the previous tick, for interpolation */
+    pub pp: V32,
+    pub pa: f32,
+
+    /* outline to collide with, a circle of radius r if there is none */
+    pub hull: Option<Rc<Hull>>,
+    /* moves far in one step, check the whole path for hits */
+    pub bullet: bool,
+
+    /* what this body collides with */
+    pub filter: Filter,
+    /* given by the engine when the body is added */
+    pub id: Option<EntityId>,
+    /* entity that made this one, the two never collide */
+    pub owner: Option<EntityId>,
+}
+
+/// Kinds of bodies, as bits for `Filter::category` and `Filter::mask`.
+pub mod layer {
+    pub const SHIP: u32 = 1 << 0;
+    pub const PROJECTILE: u32 = 1 << 1;
+    pub const ASTEROID: u32 = 1 << 2;
+    pub const PICKUP: u32 = 1 << 3;
+    pub const EFFECT: u32 = 1 << 4;
+
+    pub const ALL: u32 = !0;
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Filter {
+    /* what the body is, one of the layer bits */
+    pub category: u32,
+    /* what it collides with */
+    pub mask: u32,
+    /* reports overlaps, but nothing is pushed */
+    pub sensor: bool,
 }
 
-fn wrap(v: &mut f32, lo: f32, hi: f32) {
+impl Filter {
+    pub fn new(category: u32, mask: u32) -> Filter {
+        Filter {
+            category: category,
+            mask: mask,
+            sensor: false,
+        }
+    }
+
+    pub fn sensor(self) -> Filter {
+        Filter {
+            sensor: true,
+            ..self
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter::new(layer::ALL, layer::ALL)
+    }
+}
+
+fn cross(a: V32, b: V32) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/* a turned a quarter counter-clockwise */
+fn perp(a: V32) -> V32 {
+    vec2(-a.y, a.x)
+}
+
+/* returns how much the value was shifted */
+fn wrap(v: &mut f32, lo: f32, hi: f32) -> f32 {
     let d = hi - lo;
     if *v < lo {
         *v += d;
+        return d;
     }
     if *v > hi {
         *v -= d;
+        return -d;
+    }
+    0.0
+}
+
+/* keep the value between walls, reflecting its velocity off them */
+fn bounce(v: &mut f32, dv: &mut f32, lo: f32, hi: f32) {
+    if *v < lo {
+        *v = lo;
+        *dv = dv.abs() * REST_FACTOR;
+    }
+    if *v > hi {
+        *v = hi;
+        *dv = -dv.abs() * REST_FACTOR;
     }
 }
 
@@ -29,23 +121,41 @@ impl Body {
     pub fn init(init: Body) -> Body {
         Body {
             m: init.ρ * init.r.powi(3),
+            pp: init.p,
+            pa: init.a,
             ..init
         }
     }
 
-    pub fn think(&mut self, dt: f32) {
+    pub fn think(&mut self, dt: f32, world: &World) {
+        self.pp = self.p;
+        self.pa = self.a;
+
         self.p = self.p + self.dp * dt;
         self.a = self.a + self.da * dt;
-        if self.a > 2.0 {
-            self.a -= 2.0;
+        self.pa += wrap(&mut self.a, 0.0, 2.0);
+
+        let (lo, hi) = (world.lo(), world.hi());
+        if world.wrap_x {
+            self.pp.x += wrap(&mut self.p.x, lo.x, hi.x);
+        } else {
+            bounce(&mut self.p.x, &mut self.dp.x, lo.x + self.r, hi.x - self.r);
         }
-        if self.a < 0.0 {
-            self.a += 2.0;
+        if world.wrap_y {
+            self.pp.y += wrap(&mut self.p.y, lo.y, hi.y);
+        } else {
+            bounce(&mut self.p.y, &mut self.dp.y, lo.y + self.r, hi.y - self.r);
         }
+    }
+
+    /// Position between the previous and the current tick.
+    pub fn lerp_p(&self, alpha: f32) -> V32 {
+        self.pp + (self.p - self.pp) * alpha
+    }
 
-        /* FIXME: hardcoded world size */
-        wrap(&mut self.p.x, -350.0, 350.0);
-        wrap(&mut self.p.y, -350.0, 350.0);
+    /// Angle between the previous and the current tick.
+    pub fn lerp_a(&self, alpha: f32) -> f32 {
+        self.pa + (self.a - self.pa) * alpha
     }
 
     pub fn apply_force_abs(&mut self, f: V32) {
@@ -62,6 +172,27 @@ impl Body {
         self.apply_force_world(f, a);
     }
 
+    /// Moment of inertia, taking the body as a uniform disc.
+    pub fn inertia(&self) -> f32 {
+        self.m * self.r * self.r / 2.0
+    }
+
+    /// Angular velocity in radians per second.
+    pub fn spin(&self) -> f32 {
+        self.da * PI
+    }
+
+    /// Velocity of a point fixed to the body, `r` away from the centre.
+    pub fn velocity_at(&self, r: V32) -> V32 {
+        self.dp + perp(r) * self.spin()
+    }
+
+    /// Apply an impulse at `r` away from the centre.
+    pub fn apply_impulse(&mut self, j: V32, r: V32) {
+        self.dp = self.dp + j / self.m;
+        self.da += cross(r, j) / self.inertia() / PI;
+    }
+
     pub fn apply_torque(&mut self, t: f32) {
         self.da += t / self.m;
     }
@@ -70,6 +201,27 @@ impl Body {
         let m = M32::from_angle(rad(self.a * PI));
         m * p
     }
+
+    /// Radius of a circle around everything this body can touch with.
+    pub fn extent(&self) -> f32 {
+        match self.hull {
+            Some(ref hull) => hull.radius().max(self.r),
+            None => self.r,
+        }
+    }
+
+    /// Move back along the last step, `t` from 0 at the previous
+    /// position to 1 at the current one.
+    pub fn rewind(&mut self, t: f32) {
+        self.p = self.pp + (self.p - self.pp) * t;
+    }
+
+    fn place(&self, p: V32) -> Vec<Convex> {
+        match self.hull {
+            Some(ref hull) => hull.place(p, self.a),
+            None => vec![Convex::circle(p, self.r)],
+        }
+    }
 }
 
 impl Default for Body {
@@ -82,42 +234,185 @@ impl Default for Body {
             m: 0.0,
             dp: vec2(0.0, 0.0),
             da: 0.0,
+            μ: 0.3,
+            pp: vec2(0.0, 0.0),
+            pa: 0.0,
+            hull: None,
+            bullet: false,
+            filter: Filter::default(),
+            id: None,
+            owner: None,
         }
     }
 }
 
 const REST_FACTOR: f32 = 0.8;
-const UNIT_OF_ENERGY: f32 = 1e8;
+pub const UNIT_OF_ENERGY: f32 = 1e8;
 
+/* twice the kinetic energy of the pair, in their centre of mass frame */
 fn energy(a: &Body, b: &Body) -> f32 {
     let c_dp = (a.dp * a.m + b.dp * b.m) / (a.m + b.m);
     (a.dp - c_dp).length2() * a.m + (b.dp - c_dp).length2() * b.m
+        + a.inertia() * a.spin().powi(2) + b.inertia() * b.spin().powi(2)
 }
 
-pub fn collide(a: &mut Body, b: &mut Body) -> Option<f32> {
-    let dp = a.p - b.p;
-    let dist = dp.length() - a.r - b.r;
+/* impulse on a at a contact `ra` and `rb` away from the centres, normal
+ * pointing from b to a, zero if they are already moving apart */
+fn impulse(a: &Body, b: &Body, ra: V32, rb: V32, normal: V32, restitution: f32) -> V32 {
+    let dv = a.velocity_at(ra) - b.velocity_at(rb);
+    let vn = dv.dot(normal);
+    if vn >= 0.0 {
+        return vec2(0.0, 0.0);
+    }
+
+    /* how much velocity at the contact changes per unit of impulse along d */
+    let response = |d: V32| {
+        1.0 / a.m + 1.0 / b.m
+            + cross(ra, d).powi(2) / a.inertia()
+            + cross(rb, d).powi(2) / b.inertia()
+    };
+
+    let jn = -(1.0 + restitution) * vn / response(normal);
+
+    /* friction opposes sliding, at most enough to stop it */
+    let slide = dv - normal * vn;
+    if slide.length2() == 0.0 {
+        return normal * jn;
+    }
+    let tangent = slide.normalize();
+    let jt = (slide.length() / response(tangent)).min((a.μ * b.μ).sqrt() * jn);
+
+    normal * jn - tangent * jt
+}
+
+/* exchange momentum at a contact, returns the impulse on a */
+fn resolve(a: &mut Body, b: &mut Body, dp: V32, normal: V32, point: V32, restitution: f32) -> V32 {
+    let rb = point - b.p;
+    let ra = rb - dp;
+    let j = impulse(a, b, ra, rb, normal, restitution);
+    a.apply_impulse(j, ra);
+    b.apply_impulse(-j, rb);
+    j
+}
+
+/// What happened in a collision, as seen by the first body.
+#[derive(Copy, Clone, Debug)]
+pub struct Contact {
+    /* kinetic energy lost, in game units */
+    pub energy: f32,
+    /* middle of the overlap */
+    pub point: V32,
+    /* unit vector from the other body towards this one */
+    pub normal: V32,
+    /* impulse this body received, the other got the opposite */
+    pub impulse: V32,
+}
 
-    if dist < 0.0 {
-        if a.m == 0.0 || b.m == 0.0 {
-            return Some(0.0);
+impl Contact {
+    /// The same contact as seen by the second body.
+    pub fn flip(&self) -> Contact {
+        Contact {
+            energy: self.energy,
+            point: self.point,
+            normal: -self.normal,
+            impulse: -self.impulse,
         }
+    }
+}
 
-        let energy_before = energy(&a, &b);
+/* how deep the bodies overlap, the normal from b to a, and the contact point */
+fn overlap(a: &Body, b: &Body, dp: V32) -> Option<(f32, V32, V32)> {
+    if dp.length() >= a.extent() + b.extent() {
+        return None;
+    }
 
-        let dv = a.dp - b.dp;
-        let change = dp * dv.dot(dp) / dp.length2() * 2.0 / (a.m + b.m) * REST_FACTOR;
+    if a.hull.is_none() && b.hull.is_none() {
+        let dist = dp.length() - a.r - b.r;
+        /* right on top of each other, any way out will do */
+        let normal = if dp.length2() > 0.0 { dp.normalize() } else { vec2(1.0, 0.0) };
+        return Some((-dist, normal, b.p + normal * (b.r + dist / 2.0)));
+    }
 
-        a.dp = a.dp - change * b.m;
-        b.dp = b.dp + change * a.m;
+    /* place b next to a, so that neither straddles an edge */
+    let bp = a.p - dp;
+    hull::overlap(&a.place(a.p), &b.place(bp))
+        .map(|(depth, normal, point)| (depth, normal, point - bp + b.p))
+}
+
+/// Whether the two bodies' filters let them collide at all.
+pub fn can_collide(a: &Body, b: &Body) -> bool {
+    let owned = |a: &Body, b: &Body| a.id.is_some() && b.owner == a.id;
+
+    a.filter.mask & b.filter.category != 0
+        && b.filter.mask & a.filter.category != 0
+        && !owned(a, b) && !owned(b, a)
+}
+
+/// When `a`, moving from its previous position to the current one, first
+/// touched `b` during the last step, as a fraction of the step.
+///
+/// `b` is taken to be where it is now, which is close enough when `a` is
+/// much faster.
+pub fn sweep(a: &Body, b: &Body, world: &World) -> Option<f32> {
+    let v = a.p - a.pp;
+    let d1 = world.delta(a.p, b.p);
+    let d0 = d1 - v;
+    let reach = a.extent() + b.extent();
+
+    /* when the bounding circles overlap: |d0 + v t| < reach */
+    let (qa, qb, qc) = (v.length2(), 2.0 * d0.dot(v), d0.length2() - reach * reach);
+    if qa == 0.0 {
+        return if qc < 0.0 && overlap(a, b, d1).is_some() { Some(1.0) } else { None };
+    }
+    let disc = qb * qb - 4.0 * qa * qc;
+    if disc <= 0.0 {
+        return None;
+    }
+    let t0 = ((-qb - disc.sqrt()) / (2.0 * qa)).max(0.0);
+    let t1 = ((-qb + disc.sqrt()) / (2.0 * qa)).min(1.0);
+    if t0 >= t1 {
+        return None;
+    }
+
+    /* step through in fractions of a's size, so nothing thinner slips by */
+    let n = ((v.length() * (t1 - t0)) / a.r.max(1.0)).ceil().max(1.0) as usize;
+    for k in 0..n {
+        let t = t0 + (t1 - t0) * (k as f32 + 0.5) / n as f32;
+        if overlap(a, b, d0 + v * t).is_some() {
+            return Some(t);
+        }
+    }
+    None
+}
+
+pub fn collide(a: &mut Body, b: &mut Body, world: &World) -> Option<Contact> {
+    let dp = world.delta(a.p, b.p);
+
+    if let Some((depth, normal, point)) = overlap(a, b, dp) {
+        let mut contact = Contact {
+            energy: 0.0,
+            point: point,
+            normal: normal,
+            impulse: vec2(0.0, 0.0),
+        };
+
+        if a.m == 0.0 || b.m == 0.0 || a.filter.sensor || b.filter.sensor {
+            return Some(contact);
+        }
+
+        let energy_before = energy(&a, &b);
+
+        let j = resolve(a, b, dp, normal, point, REST_FACTOR);
 
         let energy_after = energy(&a, &b);
 
-        let correction = dp.normalize() * dist / (a.m + b.m);
+        let correction = -normal * depth / (a.m + b.m);
         a.p = a.p - correction * b.m;
         b.p = b.p + correction * a.m;
 
-        Some((energy_before - energy_after) / UNIT_OF_ENERGY)
+        contact.energy = (energy_before - energy_after) / UNIT_OF_ENERGY;
+        contact.impulse = j;
+        Some(contact)
     } else {
         None
     }
@@ -132,10 +427,192 @@ fn test_body() {
     assert_eq!(b.dp, vec2(0.5, 0.0));
     assert_eq!(b.da, 0.5);
 
-    b.think(2.0);
+    b.think(2.0, &World::default());
 
     assert_eq!(b.dp, vec2(0.5, 0.0));
     assert_eq!(b.da, 0.5);
     assert_eq!(b.p, vec2(1.0, 0.0));
     assert_eq!(b.a, 1.0);
 }
+
+#[test]
+fn test_collide_across_edges() {
+    let world = World::default();
+
+    for &(sx, sy) in &[(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0),
+                       (1.0, 1.0), (1.0, -1.0), (-1.0, 1.0), (-1.0, -1.0)] {
+        let mut a = Body::init(Body {
+            p: vec2(349.0 * sx, 349.0 * sy),
+            dp: vec2(10.0 * sx, 10.0 * sy),
+            r: 5.0,
+            ..Body::default()
+        });
+        let mut b = Body::init(Body {
+            p: vec2(-349.0 * sx, -349.0 * sy),
+            r: 5.0,
+            ..Body::default()
+        });
+
+        assert!(world.distance(a.p, b.p) < 4.0);
+        assert!(collide(&mut a, &mut b, &world).is_some());
+
+        /* a was moving towards b across the edge, and got pushed back */
+        assert!(a.dp.dot(vec2(sx, sy)) < 10.0);
+        assert!(b.dp.dot(vec2(sx, sy)) > 0.0);
+        assert!(world.distance(a.p, b.p) >= 10.0 - 1e-3);
+    }
+
+    let mut a = Body::init(Body { p: vec2(349.0, 0.0), r: 5.0, ..Body::default() });
+    let mut b = Body::init(Body { p: vec2(-300.0, 0.0), r: 5.0, ..Body::default() });
+    assert!(collide(&mut a, &mut b, &world).is_none());
+
+    let walled = world.walled();
+    let mut a = Body::init(Body { p: vec2(349.0, 0.0), r: 5.0, ..Body::default() });
+    let mut b = Body::init(Body { p: vec2(-349.0, 0.0), r: 5.0, ..Body::default() });
+    assert!(collide(&mut a, &mut b, &walled).is_none());
+
+    /* same centre, still pushed apart */
+    let mut a = Body::init(Body { p: vec2(10.0, 0.0), r: 5.0, ..Body::default() });
+    let mut b = Body::init(Body { p: vec2(10.0, 0.0), r: 5.0, ..Body::default() });
+    let contact = collide(&mut a, &mut b, &world).unwrap();
+    assert!(contact.normal.x.is_finite() && contact.point.x.is_finite());
+    assert!(a.p.x.is_finite() && a.p != b.p);
+}
+
+#[test]
+fn test_walls() {
+    let world = World::new(200.0, 100.0).walled();
+    let mut b = Body::init(Body { p: vec2(90.0, 0.0), dp: vec2(100.0, -10.0), r: 5.0, ..Body::default() });
+
+    b.think(0.1, &world);
+    assert_eq!(b.p.x, 95.0);
+    assert!(b.dp.x < 0.0);
+    assert_eq!(b.dp.y, -10.0);
+}
+
+#[test]
+fn test_collide_hulls() {
+    let world = World::default();
+    let square = || Some(Rc::new(Hull::from_polar(&[[0.25, 10.0], [0.75, 10.0], [1.25, 10.0], [1.75, 10.0], [0.25, 10.0]])));
+
+    /* circles of radius 5 don't touch, the corners across the edge do */
+    let mut a = Body::init(Body { p: vec2(345.0, 0.0), r: 5.0, hull: square(), ..Body::default() });
+    let mut b = Body::init(Body { p: vec2(-342.0, 3.0), dp: vec2(-10.0, 0.0), r: 5.0, hull: square(), ..Body::default() });
+    let c = collide(&mut a, &mut b, &world).unwrap();
+    assert!((c.normal - vec2(-1.0, 0.0)).length() < 1e-4);
+    assert!(c.point.x.abs() > 340.0);
+    assert!(b.dp.x > -10.0);
+
+    /* turned, the same squares miss */
+    let mut a = Body::init(Body { p: vec2(345.0, 0.0), a: 0.25, r: 5.0, hull: square(), ..Body::default() });
+    let mut b = Body::init(Body { p: vec2(-342.0, 8.0), a: 0.25, r: 5.0, hull: square(), ..Body::default() });
+    assert!(collide(&mut a, &mut b, &world).is_none());
+}
+
+#[cfg(test)]
+fn angular_momentum(bodies: &[&Body]) -> f32 {
+    bodies.iter().fold(0.0, |sum, b| sum + cross(b.p, b.dp * b.m) + b.inertia() * b.spin())
+}
+
+#[test]
+fn test_spin_and_friction() {
+    let world = World::default();
+    let square = || Some(Rc::new(Hull::from_polar(&[[0.25, 10.0], [0.75, 10.0], [1.25, 10.0], [1.75, 10.0], [0.25, 10.0]])));
+
+    for &(restitution, μ) in &[(1.0, 0.0), (REST_FACTOR, 0.0), (REST_FACTOR, 0.5), (0.0, 1.0)] {
+        /* a glancing blow, well off the centre line */
+        let mut a = Body::init(Body { p: vec2(0.0, 0.0), dp: vec2(30.0, 5.0), r: 7.0, μ: μ, hull: square(), ..Body::default() });
+        let mut b = Body::init(Body { p: vec2(12.0, 9.0), da: 0.1, r: 7.0, ρ: 2.0, μ: μ, ..Body::default() });
+
+        let dp = world.delta(a.p, b.p);
+        let (_, normal, point) = overlap(&a, &b, dp).unwrap();
+
+        let momentum = a.dp * a.m + b.dp * b.m;
+        let angular = angular_momentum(&[&a, &b]);
+        let before = energy(&a, &b);
+
+        let j = resolve(&mut a, &mut b, dp, normal, point, restitution);
+        let after = energy(&a, &b);
+
+        assert!(j.length() > 0.0);
+        assert!((a.dp * a.m + b.dp * b.m - momentum).length() < momentum.length() * 1e-5);
+        assert!((angular_momentum(&[&a, &b]) - angular).abs() < angular.abs() * 1e-4);
+        assert!(after <= before * (1.0 + 1e-5));
+        if restitution == 1.0 && μ == 0.0 {
+            assert!((after - before).abs() < before * 1e-4);
+        }
+
+        /* the hit was off centre, so a now turns */
+        assert!(a.da != 0.0);
+    }
+
+    /* through collide, energy lost is what gets reported */
+    let mut a = Body::init(Body { p: vec2(0.0, 0.0), dp: vec2(30.0, 0.0), r: 7.0, hull: square(), ..Body::default() });
+    let mut b = Body::init(Body { p: vec2(12.0, 9.0), r: 7.0, ..Body::default() });
+    let before = energy(&a, &b);
+    let c = collide(&mut a, &mut b, &world).unwrap();
+    assert!(c.energy > 0.0);
+    assert!((before - energy(&a, &b) - c.energy * UNIT_OF_ENERGY).abs() < before * 1e-4);
+}
+
+#[test]
+fn test_sweep() {
+    let world = World::default();
+    let mut a = Body::init(Body { p: vec2(-30.0, 0.0), r: 5.0, bullet: true, ..Body::default() });
+    let b = Body::init(Body { p: vec2(0.0, 1.0), r: 2.0, ..Body::default() });
+
+    /* jumps clean over b in one step */
+    a.dp = vec2(1800.0, 0.0);
+    a.think(1.0 / 30.0, &world);
+    assert!(a.p.x > 20.0);
+    assert!(overlap(&a, &b, world.delta(a.p, b.p)).is_none());
+
+    let t = sweep(&a, &b, &world).unwrap();
+    a.rewind(t);
+    assert!(a.p.x > -7.0 && a.p.x < 7.0);
+    assert!(collide(&mut a, &mut Body::init(Body { p: b.p, r: b.r, ..Body::default() }), &world).is_some());
+
+    /* passing by, and not reaching */
+    let mut a = Body::init(Body { p: vec2(-30.0, 10.0), dp: vec2(1800.0, 0.0), r: 5.0, ..Body::default() });
+    a.think(1.0 / 30.0, &world);
+    assert!(sweep(&a, &b, &world).is_none());
+    let mut a = Body::init(Body { p: vec2(-90.0, 0.0), dp: vec2(1800.0, 0.0), r: 5.0, ..Body::default() });
+    a.think(1.0 / 30.0, &world);
+    assert!(sweep(&a, &b, &world).is_none());
+}
+
+#[test]
+fn test_filter() {
+    let world = World::default();
+    let body = |category, mask, id, owner: Option<u32>| Body::init(Body {
+        r: 5.0,
+        filter: Filter::new(category, mask),
+        id: Some(EntityId::new(id, 0)),
+        owner: owner.map(|o| EntityId::new(o, 0)),
+        ..Body::default()
+    });
+
+    let ship = body(layer::SHIP, layer::ALL, 1, None);
+    let other = body(layer::SHIP, layer::ALL, 2, None);
+    let beam = body(layer::PROJECTILE, layer::ALL & !layer::PROJECTILE, 3, Some(1));
+    let beam2 = body(layer::PROJECTILE, layer::ALL & !layer::PROJECTILE, 4, Some(2));
+
+    assert!(can_collide(&ship, &other));
+    assert!(!can_collide(&ship, &beam));
+    assert!(!can_collide(&beam, &ship));
+    assert!(can_collide(&other, &beam));
+    assert!(!can_collide(&beam, &beam2));
+
+    /* sensors see the overlap, but don't move */
+    let mut a = body(layer::PICKUP, layer::SHIP, 5, None);
+    a.filter = a.filter.sensor();
+    a.dp = vec2(10.0, 0.0);
+    let mut b = body(layer::SHIP, layer::ALL, 6, None);
+    b.p = vec2(8.0, 0.0);
+    assert!(can_collide(&a, &b));
+    let c = collide(&mut a, &mut b, &world).unwrap();
+    assert_eq!(c.energy, 0.0);
+    assert_eq!(a.dp, vec2(10.0, 0.0));
+    assert_eq!(b.dp, vec2(0.0, 0.0));
+    assert_eq!(b.p, vec2(8.0, 0.0));
+}
@@ -8,6 +8,12 @@ use cgmath::{ Decomposed, Rotation3, Basis3, SquareMatrix, Matrix4, vec3, rad };
 
 use std::f32::consts::PI;
 
+use super::{ Render, Shape, ShapeType, Viewport, CLEAR_COLOR, projection };
+use world::World;
+
+/* longest side of the window, in pixels */
+const WINDOW_SIZE: f32 = 700.0;
+
 pub mod backend {
     use gfx_device_gl;
 
@@ -18,14 +24,14 @@ pub mod backend {
 }
 
 gfx_vertex_struct! {
-    Vertex {
+    GlVertex {
         pos: [f32; 3] = "vertex_pos",
     }
 }
 
-impl Vertex {
-    pub fn new(p: [f32; 2]) -> Vertex {
-        Vertex {
+impl GlVertex {
+    fn new(p: [f32; 2]) -> GlVertex {
+        GlVertex {
             pos: [ p[0], p[1], 0.0 ],
         }
     }
@@ -33,7 +39,7 @@ impl Vertex {
 
 gfx_pipeline!{
     main_pline {
-        vbuf: gfx::VertexBuffer<Vertex> = (),
+        vbuf: gfx::VertexBuffer<GlVertex> = (),
         color: gfx::Global<[f32; 4]> = "shape_color",
         trans: gfx::Global<[[f32; 4]; 4]> = "shape_trans",
         time: gfx::Global<f32> = "effect_time",
@@ -41,38 +47,42 @@ gfx_pipeline!{
     }
 }
 
-pub fn from_polar(p: &[f32; 2]) -> [f32; 2] {
-    [
-        (PI * p[0]).cos() * p[1],
-        (PI * p[0]).sin() * p[1],
-    ]
-}
-
-pub enum ShapeType {
-    Game,
-    UI
-}
-
-pub struct Shape {
-    ty: ShapeType,
+/* GPU side of a render::Shape */
+struct GlShape {
     data: main_pline::Data<backend::Resources>,
     slice: gfx::Slice<backend::Resources>,
-    transform: Matrix4<f32>,
 }
 
-impl Shape {
-    pub fn set_transform(&mut self, x: f32, y: f32, r: f32) {
-        let d = Decomposed {
-            scale: 1.0,
-            rot: Basis3::from_angle_z(rad(r * PI)),
-            disp: vec3(x, y, 0.0),
+impl GlShape {
+    fn new(factory: &mut backend::Factory,
+           targ_color: &gfx::handle::RenderTargetView<backend::Resources, gfx::format::Rgba8>,
+           shape: &Shape) -> GlShape
+    {
+        let vdata: Vec<_> = shape.vertices().iter()
+            .map(|v| GlVertex { pos: v.pos })
+            .collect();
+
+        let (vbuf, slice) = factory.create_vertex_buffer(&vdata);
+
+        let data = main_pline::Data {
+            vbuf: vbuf,
+            color: shape.color(),
+            time: 0.0,
+            trans: Matrix4::identity().into(),
+            targ_color: targ_color.clone(),
         };
-        self.transform = Matrix4::from(d).into();
+
+        GlShape {
+            data: data,
+            slice: slice,
+        }
     }
 }
 
 pub struct Renderer {
+    world: World,
     transform: Matrix4<f32>,
+    viewport: Viewport,
 
     window: glutin::Window,
     device: backend::Device,
@@ -102,12 +112,14 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub fn new() -> Self {
+    pub fn new(world: &World) -> Self {
+        let scl = WINDOW_SIZE / world.width.max(world.height);
+        let (width, height) = ((world.width * scl) as u32, (world.height * scl) as u32);
+
         let builder = glutin::WindowBuilder::new()
             .with_title("Roids".to_owned())
             .with_gl(glutin::GL_CORE)
-            /* FIXME: hardcoded world size */
-            .with_dimensions(600, 600)
+            .with_dimensions(width, height)
             .with_vsync();
 
         let (window, device, mut factory, targ_color, targ_depth) =
@@ -146,10 +158,10 @@ impl Renderer {
             ).unwrap();
 
             let vertices = [
-                Vertex::new([-1.0, -1.0]),
-                Vertex::new([-1.0,  1.0]),
-                Vertex::new([ 1.0, -1.0]),
-                Vertex::new([ 1.0,  1.0]),
+                GlVertex::new([-1.0, -1.0]),
+                GlVertex::new([-1.0,  1.0]),
+                GlVertex::new([ 1.0, -1.0]),
+                GlVertex::new([ 1.0,  1.0]),
             ];
 
             let (vbuf, slice) = factory.create_vertex_buffer(&vertices);
@@ -167,14 +179,10 @@ impl Renderer {
 
         let command_buffer = factory.create_command_buffer();
 
-        let transform = {
-            /* FIXME: hardcoded world size */
-            let scl = 1.0 / 300.0;
-            Matrix4::from_scale(scl)
-        };
-
         Renderer {
-            transform: transform,
+            world: *world,
+            transform: projection(world, width as f32, height as f32),
+            viewport: Viewport::new(world, width as f32, height as f32),
             window: window,
             device: device,
             factory: factory,
@@ -191,45 +199,44 @@ impl Renderer {
         &mut self.window
     }
 
-    pub fn create_shape(&mut self, ty: ShapeType, color: [f32; 4], vertices: &[Vertex]) -> Shape {
-        let (vbuf, slice) = self.factory.create_vertex_buffer(vertices);
-
-        let data = main_pline::Data {
-            vbuf: vbuf,
-            color: color,
-            time: 0.0,
-            trans: Matrix4::identity().into(),
-            targ_color: self.targ_color.clone(),
-        };
+    /// Follow the window size, call on `Event::Resized`.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        gfx_window_glutin::update_views(&self.window, &mut self.targ_color, &mut self.targ_depth);
+        self.boom_fx.data.targ_color = self.targ_color.clone();
+        self.transform = projection(&self.world, width as f32, height as f32);
+        self.viewport = Viewport::new(&self.world, width as f32, height as f32);
+    }
 
-        Shape {
-            ty: ty,
-            data: data,
-            slice: slice,
-            transform: Matrix4::identity(),
-        }
+    /// What the window shows, to map the mouse into the world.
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
     }
+}
 
-    pub fn create_shape_simple(&mut self, color: [f32; 4], shape: &[[f32; 2]]) -> Shape {
-        let vdata: Vec<_> = shape.iter()
-            .map(from_polar)
-            .map(Vertex::new)
-            .collect();
-        self.create_shape(ShapeType::Game, color, &vdata)
+impl Render for Renderer {
+    fn clear(&mut self) {
+        self.encoder.clear(&self.targ_color, CLEAR_COLOR);
+        self.encoder.clear_depth(&self.targ_depth, 1.0);
     }
 
-    pub fn draw_shape(&mut self, shape: &mut Shape) {
-        shape.data.trans = (self.transform * shape.transform).into();
+    fn draw_shape(&mut self, shape: &mut Shape) {
+        let trans: [[f32; 4]; 4] = (self.transform * *shape.transform()).into();
 
-        let pline_state = match shape.ty {
+        let pline_state = match shape.ty() {
             ShapeType::Game => &self.main_state,
             ShapeType::UI => &self.ui_state,
         };
 
-        self.encoder.draw(&shape.slice, pline_state, &shape.data);
+        let factory = &mut self.factory;
+        let targ_color = &self.targ_color;
+        let gl = shape.cached(|shape| GlShape::new(factory, targ_color, shape));
+        gl.data.trans = trans;
+        gl.data.targ_color = targ_color.clone();
+
+        self.encoder.draw(&gl.slice, pline_state, &gl.data);
     }
 
-    pub fn draw_boom(&mut self, x: f32, y: f32, a: f32, r: f32, t: f32) {
+    fn draw_boom(&mut self, x: f32, y: f32, a: f32, r: f32, t: f32) {
         let d = Decomposed {
             scale: r,
             rot: Basis3::from_angle_z(rad(a * PI)),
@@ -240,15 +247,9 @@ impl Renderer {
         self.boom_fx.encode(&mut self.encoder);
     }
 
-    pub fn clear(&mut self) {
-        self.encoder.clear(&self.targ_color, [ 0.01, 0.01, 0.02, 1.0 ]);
-        self.encoder.clear_depth(&self.targ_depth, 1.0);
-    }
-
-    pub fn finish(&mut self) {
+    fn finish(&mut self) {
         self.encoder.flush(&mut self.device);
         self.window.swap_buffers().unwrap();
         self.device.cleanup();
     }
 }
-
@@ -0,0 +1,225 @@
+use std::any::Any;
+use std::f32::consts::PI;
+
+use cgmath::{ Decomposed, Rotation3, Basis3, SquareMatrix, Matrix4, Vector2, vec2, vec3, rad };
+
+use world::World;
+
+pub mod gl;
+pub mod soft;
+pub mod text;
+
+pub const CLEAR_COLOR: [f32; 4] = [ 0.01, 0.01, 0.02, 1.0 ];
+
+/* ships and HUD panels of each player slot */
+const PLAYER_COLORS: &'static [[f32; 4]] = &[
+    [ 1.0, 1.0, 1.0, 1.0 ],
+    [ 1.0, 0.6, 0.3, 1.0 ],
+    [ 0.4, 0.9, 0.5, 1.0 ],
+    [ 0.8, 0.5, 1.0, 1.0 ],
+];
+
+/// Color of a player slot, repeating past the last one.
+pub fn player_color(slot: usize) -> [f32; 4] {
+    PLAYER_COLORS[slot % PLAYER_COLORS.len()]
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Vertex {
+    pub pos: [f32; 3],
+}
+
+impl Vertex {
+    pub fn new(p: [f32; 2]) -> Vertex {
+        Vertex {
+            pos: [ p[0], p[1], 0.0 ],
+        }
+    }
+}
+
+pub fn from_polar(p: &[f32; 2]) -> [f32; 2] {
+    [
+        (PI * p[0]).cos() * p[1],
+        (PI * p[0]).sin() * p[1],
+    ]
+}
+
+/// World to clip space transform showing the whole world in a viewport
+/// of the given size, keeping the aspect ratio.
+pub fn projection(world: &World, width: f32, height: f32) -> Matrix4<f32> {
+    let (sx, sy) = if width / height > world.width / world.height {
+        let sy = 2.0 / world.height;
+        (sy * height / width, sy)
+    } else {
+        let sx = 2.0 / world.width;
+        (sx, sx * width / height)
+    };
+    Matrix4::from_nonuniform_scale(sx, sy, 1.0)
+}
+
+/// Window of a given size in pixels showing the world as `projection`
+/// puts it there, for finding out what the mouse points at.
+#[derive(Copy, Clone, Debug)]
+pub struct Viewport {
+    /* world to clip scale, all `projection` does */
+    scale: Vector2<f32>,
+    /* corner of the world, the others are mirrored around the origin */
+    hi: Vector2<f32>,
+    width: f32,
+    height: f32,
+}
+
+impl Viewport {
+    pub fn new(world: &World, width: f32, height: f32) -> Viewport {
+        let m = projection(world, width, height);
+        Viewport {
+            scale: vec2(m.x.x, m.y.y),
+            hi: world.hi(),
+            width: width,
+            height: height,
+        }
+    }
+
+    /// World position under a pixel, counting from the top left corner.
+    ///
+    /// Pixels on the bars or outside the window land on the nearest edge.
+    pub fn to_world(&self, x: f32, y: f32) -> Vector2<f32> {
+        let clip = vec2(2.0 * x / self.width - 1.0, 1.0 - 2.0 * y / self.height);
+        let (x, y) = (clip.x / self.scale.x, clip.y / self.scale.y);
+        vec2(x.max(-self.hi.x).min(self.hi.x), y.max(-self.hi.y).min(self.hi.y))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ShapeType {
+    /* line strip in world coordinates */
+    Game,
+    /* triangle strip, also in world coordinates */
+    UI
+}
+
+/// Backend independent description of something to draw.
+///
+/// Backends are free to keep their own data (vertex buffers and such)
+/// in the shape, see `cached`.
+pub struct Shape {
+    ty: ShapeType,
+    color: [f32; 4],
+    vertices: Vec<Vertex>,
+    transform: Matrix4<f32>,
+    cache: Option<Box<Any>>,
+}
+
+impl Shape {
+    pub fn new(ty: ShapeType, color: [f32; 4], vertices: &[Vertex]) -> Shape {
+        Shape {
+            ty: ty,
+            color: color,
+            vertices: vertices.to_vec(),
+            transform: Matrix4::identity(),
+            cache: None,
+        }
+    }
+
+    pub fn set_transform(&mut self, x: f32, y: f32, r: f32) {
+        let d = Decomposed {
+            scale: 1.0,
+            rot: Basis3::from_angle_z(rad(r * PI)),
+            disp: vec3(x, y, 0.0),
+        };
+        self.transform = Matrix4::from(d).into();
+    }
+
+    pub fn ty(&self) -> ShapeType {
+        self.ty
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        self.color
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn transform(&self) -> &Matrix4<f32> {
+        &self.transform
+    }
+
+    /// Get backend data attached to this shape, creating it on first use.
+    pub fn cached<T, F>(&mut self, make: F) -> &mut T
+        where T: Any, F: FnOnce(&Shape) -> T
+    {
+        let fresh = match self.cache {
+            Some(ref data) => !data.is::<T>(),
+            None => true,
+        };
+
+        if fresh {
+            let data = make(self);
+            self.cache = Some(Box::new(data));
+        }
+
+        self.cache.as_mut().and_then(|data| data.downcast_mut::<T>()).unwrap()
+    }
+}
+
+/// Everything entities and the HUD need to put themselves on screen.
+pub trait Render {
+    fn clear(&mut self);
+    fn draw_shape(&mut self, shape: &mut Shape);
+    fn draw_boom(&mut self, x: f32, y: f32, a: f32, r: f32, t: f32);
+    fn finish(&mut self);
+
+    fn create_shape(&mut self, ty: ShapeType, color: [f32; 4], vertices: &[Vertex]) -> Shape {
+        Shape::new(ty, color, vertices)
+    }
+
+    fn create_shape_simple(&mut self, color: [f32; 4], shape: &[[f32; 2]]) -> Shape {
+        let vdata: Vec<_> = shape.iter()
+            .map(from_polar)
+            .map(Vertex::new)
+            .collect();
+        self.create_shape(ShapeType::Game, color, &vdata)
+    }
+}
+
+/// Renderer that draws nothing, for running the simulation headless.
+pub struct Null;
+
+impl Render for Null {
+    fn clear(&mut self) {}
+    fn draw_shape(&mut self, _: &mut Shape) {}
+    fn draw_boom(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32) {}
+    fn finish(&mut self) {}
+}
+
+#[test]
+fn test_projection() {
+    use cgmath::Vector4;
+
+    let world = World::new(800.0, 400.0);
+    let corner = Vector4::new(400.0, 200.0, 0.0, 1.0);
+
+    /* same aspect, fills the viewport */
+    let c = projection(&world, 200.0, 100.0) * corner;
+    assert_eq!((c.x, c.y), (1.0, 1.0));
+
+    /* taller viewport, bars above and below */
+    let c = projection(&world, 100.0, 100.0) * corner;
+    assert_eq!((c.x, c.y), (1.0, 0.5));
+
+    /* wider viewport, bars on the sides */
+    let c = projection(&world, 400.0, 100.0) * corner;
+    assert_eq!((c.x, c.y), (0.5, 1.0));
+
+    /* and back from pixels, y going down the window */
+    let view = Viewport::new(&world, 400.0, 100.0);
+    assert_eq!(view.to_world(200.0, 50.0), vec2(0.0, 0.0));
+    assert_eq!(view.to_world(300.0, 0.0), vec2(400.0, 200.0));
+    assert_eq!(view.to_world(100.0, 100.0), vec2(-400.0, -200.0));
+
+    /* the bars and the rest of the screen stick to the edges */
+    assert_eq!(view.to_world(0.0, 25.0), vec2(-400.0, 100.0));
+    assert_eq!(view.to_world(1e6, -1e6), vec2(400.0, 200.0));
+}
@@ -0,0 +1,402 @@
+use std::io::{ self, Write };
+use std::fs::File;
+use std::path::Path;
+use std::f32::consts::PI;
+
+use cgmath::{ Decomposed, Rotation3, Basis3, SquareMatrix, Matrix4, Vector4, vec3, rad };
+
+use super::{ Render, Shape, ShapeType, CLEAR_COLOR, projection };
+use world::World;
+
+/// CPU renderer drawing into an RGBA buffer, for tests and screenshots.
+pub struct Renderer {
+    transform: Matrix4<f32>,
+
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 4]>,
+}
+
+fn to_rgba(c: [f32; 4]) -> [u8; 4] {
+    let mut out = [0; 4];
+    for i in 0..4 {
+        out[i] = (c[i].max(0.0).min(1.0) * 255.0).round() as u8;
+    }
+    out
+}
+
+/* doubled signed area of abp, sign tells which side of ab is p on */
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/* port of boom_frag.glsl */
+fn boom_color(u: f32, v: f32, t: f32) -> Option<[f32; 4]> {
+    const GROW: f32 = 0.0;
+    const THIN: f32 = 0.1;
+    const DONE: f32 = 0.3;
+
+    let l = (u * u + v * v).sqrt();
+
+    if t < THIN {
+        let time = (t - GROW) / (THIN - GROW);
+        if l < time * 0.9 {
+            return Some([1.0, 1.0, 1.0 - time, 1.0]);
+        }
+    } else if t < DONE {
+        let time = (t - THIN) / (DONE - THIN);
+        let edge = v.asin().cos() * (1.0 - time * 2.0);
+        let s = 1.0 - time;
+
+        if (l < 0.9 && u < edge) || (l - 0.9).abs() < s / 10.0 {
+            return Some([s.sqrt(), s, 0.0, 1.0]);
+        }
+    }
+
+    None
+}
+
+impl Renderer {
+    pub fn new(world: &World, width: usize, height: usize) -> Renderer {
+        assert!(width > 0 && height > 0, "software renderer needs at least one pixel, not {}x{}", width, height);
+        Renderer {
+            transform: projection(world, width as f32, height as f32),
+            width: width,
+            height: height,
+            pixels: vec![to_rgba(CLEAR_COLOR); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        self.pixels[y * self.width + x]
+    }
+
+    fn plot(&mut self, x: i32, y: i32, color: [u8; 4]) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            self.pixels[y as usize * self.width + x as usize] = color;
+        }
+    }
+
+    /* clip space to pixel coordinates, y pointing down */
+    fn to_screen(&self, m: &Matrix4<f32>, x: f32, y: f32) -> [f32; 2] {
+        let c = *m * Vector4::new(x, y, 0.0, 1.0);
+        [
+            (c.x + 1.0) * 0.5 * self.width as f32,
+            (1.0 - c.y) * 0.5 * self.height as f32,
+        ]
+    }
+
+    fn from_screen(&self, x: f32, y: f32) -> [f32; 2] {
+        [
+            x / self.width as f32 * 2.0 - 1.0,
+            1.0 - y / self.height as f32 * 2.0,
+        ]
+    }
+
+    fn line(&mut self, a: [f32; 2], b: [f32; 2], color: [u8; 4]) {
+        let dx = b[0] - a[0];
+        let dy = b[1] - a[1];
+        let n = dx.abs().max(dy.abs()).ceil().max(1.0) as i32;
+
+        for i in 0..n + 1 {
+            let t = i as f32 / n as f32;
+            self.plot((a[0] + dx * t).floor() as i32, (a[1] + dy * t).floor() as i32, color);
+        }
+    }
+
+    /* bounding box of points, clamped to the buffer */
+    fn bounds(&self, pts: &[[f32; 2]]) -> (i32, i32, i32, i32) {
+        let mut lo = [::std::f32::MAX; 2];
+        let mut hi = [::std::f32::MIN; 2];
+        for p in pts {
+            for i in 0..2 {
+                lo[i] = lo[i].min(p[i]);
+                hi[i] = hi[i].max(p[i]);
+            }
+        }
+
+        (lo[0].floor().max(0.0) as i32,
+         lo[1].floor().max(0.0) as i32,
+         hi[0].ceil().min(self.width as f32 - 1.0) as i32,
+         hi[1].ceil().min(self.height as f32 - 1.0) as i32)
+    }
+
+    fn triangle(&mut self, a: [f32; 2], b: [f32; 2], c: [f32; 2], color: [u8; 4]) {
+        if edge(a, b, c) == 0.0 {
+            return;
+        }
+
+        let (x0, y0, x1, y1) = self.bounds(&[a, b, c]);
+        for y in y0..y1 + 1 {
+            for x in x0..x1 + 1 {
+                let p = [x as f32 + 0.5, y as f32 + 0.5];
+                let w0 = edge(b, c, p);
+                let w1 = edge(c, a, p);
+                let w2 = edge(a, b, p);
+                if (w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0) || (w0 <= 0.0 && w1 <= 0.0 && w2 <= 0.0) {
+                    self.plot(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Encode the current frame as an RGBA PNG.
+    pub fn write_png<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut raw = Vec::with_capacity((self.width * 4 + 1) * self.height);
+        for row in self.pixels.chunks(self.width) {
+            /* filter type: none */
+            raw.push(0);
+            for p in row {
+                raw.extend_from_slice(p);
+            }
+        }
+
+        let mut ihdr = Vec::new();
+        push_u32(&mut ihdr, self.width as u32);
+        push_u32(&mut ihdr, self.height as u32);
+        /* 8 bit depth, RGBA, deflate, no filters, no interlace */
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        try!(out.write_all(b"\x89PNG\r\n\x1a\n"));
+        try!(write_chunk(out, b"IHDR", &ihdr));
+        try!(write_chunk(out, b"IDAT", &zlib_stored(&raw)));
+        try!(write_chunk(out, b"IEND", &[]));
+        Ok(())
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = try!(File::create(path));
+        self.write_png(&mut file)
+    }
+}
+
+impl Render for Renderer {
+    fn clear(&mut self) {
+        let color = to_rgba(CLEAR_COLOR);
+        for p in self.pixels.iter_mut() {
+            *p = color;
+        }
+    }
+
+    fn draw_shape(&mut self, shape: &mut Shape) {
+        let m = self.transform * *shape.transform();
+        let color = to_rgba(shape.color());
+        let pts: Vec<_> = shape.vertices().iter()
+            .map(|v| self.to_screen(&m, v.pos[0], v.pos[1]))
+            .collect();
+
+        match shape.ty() {
+            ShapeType::Game => {
+                for w in pts.windows(2) {
+                    self.line(w[0], w[1], color);
+                }
+            }
+            ShapeType::UI => {
+                for w in pts.windows(3) {
+                    self.triangle(w[0], w[1], w[2], color);
+                }
+            }
+        }
+    }
+
+    fn draw_boom(&mut self, x: f32, y: f32, a: f32, r: f32, t: f32) {
+        let d = Decomposed {
+            scale: r,
+            rot: Basis3::from_angle_z(rad(a * PI)),
+            disp: vec3(x, y, 0.0),
+        };
+        let m = self.transform * Matrix4::from(d);
+        let inv = match m.invert() {
+            Some(inv) => inv,
+            None => return,
+        };
+
+        let corners = [
+            self.to_screen(&m, -1.0, -1.0),
+            self.to_screen(&m, -1.0,  1.0),
+            self.to_screen(&m,  1.0, -1.0),
+            self.to_screen(&m,  1.0,  1.0),
+        ];
+
+        let (x0, y0, x1, y1) = self.bounds(&corners);
+        for py in y0..y1 + 1 {
+            for px in x0..x1 + 1 {
+                let c = self.from_screen(px as f32 + 0.5, py as f32 + 0.5);
+                let uv = inv * Vector4::new(c[0], c[1], 0.0, 1.0);
+                if uv.x.abs() > 1.0 || uv.y.abs() > 1.0 {
+                    continue;
+                }
+                if let Some(color) = boom_color(uv.x, uv.y, t) {
+                    self.plot(px, py, to_rgba(color));
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) {}
+}
+
+fn push_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&[(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]);
+}
+
+fn crc32(data: &[&[u8]]) -> u32 {
+    let mut table = [0u32; 256];
+    for n in 0..256 {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+        }
+        table[n] = c;
+    }
+
+    let mut crc = !0u32;
+    for part in data {
+        for &b in part.iter() {
+            crc = table[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &x in data {
+        a = (a + x as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/* zlib stream made of uncompressed deflate blocks */
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(if last { 1 } else { 0 });
+        out.extend_from_slice(&[len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]);
+        out.extend_from_slice(block);
+    }
+
+    push_u32(&mut out, adler32(data));
+    out
+}
+
+fn write_chunk<W: Write>(out: &mut W, ty: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut head = Vec::new();
+    push_u32(&mut head, data.len() as u32);
+    let mut tail = Vec::new();
+    push_u32(&mut tail, crc32(&[ty, data]));
+
+    try!(out.write_all(&head));
+    try!(out.write_all(ty));
+    try!(out.write_all(data));
+    out.write_all(&tail)
+}
+
+#[test]
+fn test_soft_shapes() {
+    let mut r = Renderer::new(&World::new(600.0, 600.0), 60, 60);
+    let bg = r.pixel(0, 0);
+
+    /* horizontal line through the middle, from x=-150 to x=150 */
+    let mut line = r.create_shape_simple([1.0; 4], &[ [1.0, 150.0], [0.0, 150.0] ]);
+    line.set_transform(0.0, -5.0, 0.0);
+    r.draw_shape(&mut line);
+
+    assert_eq!(r.pixel(30, 30), [255; 4]);
+    assert_eq!(r.pixel(20, 30), [255; 4]);
+    assert_eq!(r.pixel(5, 30), bg);
+    assert_eq!(r.pixel(30, 20), bg);
+
+    /* UI quad covering the top left quarter */
+    use super::Vertex;
+    let mut quad = r.create_shape(ShapeType::UI, [0.0, 0.0, 1.0, 1.0], &[
+        Vertex::new([-300.0, 0.0]),
+        Vertex::new([-300.0, 300.0]),
+        Vertex::new([0.0, 0.0]),
+        Vertex::new([0.0, 300.0]),
+    ]);
+    r.draw_shape(&mut quad);
+
+    assert_eq!(r.pixel(10, 10), [0, 0, 255, 255]);
+    assert_eq!(r.pixel(50, 10), bg);
+    assert_eq!(r.pixel(10, 50), bg);
+
+    r.clear();
+    assert_eq!(r.pixel(10, 10), bg);
+
+    r.draw_boom(0.0, 0.0, 0.0, 30.0, 0.05);
+    assert_eq!(r.pixel(30, 30), [255, 255, 128, 255]);
+    assert_eq!(r.pixel(30, 20), bg);
+}
+
+#[test]
+fn test_soft_frame() {
+    use super::Vertex;
+
+    /* what a small frame should come out as, one character per pixel */
+    const GOLDEN: &'static [&'static str] = &[
+        "bbb.............",
+        "bb..........#...",
+        "b..........#....",
+        "..........#.....",
+        ".........#......",
+        "........#.......",
+        ".......#........",
+        "......#.........",
+        ".....#.....yy...",
+        "....#......yy...",
+        "...#............",
+        "................",
+    ];
+
+    let mut r = Renderer::new(&World::new(160.0, 120.0), 16, 12);
+    let mut line = r.create_shape_simple([1.0; 4], &[ [1.0, 60.0], [0.0, 60.0] ]);
+    line.set_transform(0.0, 0.0, 0.25);
+    r.draw_shape(&mut line);
+    let mut corner = r.create_shape(ShapeType::UI, [0.0, 0.0, 1.0, 1.0], &[
+        Vertex::new([-80.0, 20.0]),
+        Vertex::new([-80.0, 60.0]),
+        Vertex::new([-40.0, 60.0]),
+    ]);
+    r.draw_shape(&mut corner);
+    r.draw_boom(40.0, -30.0, 0.0, 25.0, 0.05);
+
+    let colors = [('.', to_rgba(CLEAR_COLOR)), ('#', [255; 4]), ('b', [0, 0, 255, 255]), ('y', [255, 255, 128, 255])];
+    let frame: Vec<String> = (0..r.height()).map(|y| (0..r.width()).map(|x| {
+        let pixel = r.pixel(x, y);
+        colors.iter().find(|c| c.1 == pixel).map_or('?', |c| c.0)
+    }).collect()).collect();
+    assert_eq!(frame, GOLDEN);
+}
+
+#[test]
+fn test_soft_png() {
+    let r = Renderer::new(&World::default(), 3, 2);
+    let mut png = Vec::new();
+    r.write_png(&mut png).unwrap();
+
+    let raw_len = (3 * 4 + 1) * 2;
+    let idat_len = 2 + 5 + raw_len + 4;
+    assert_eq!(png.len(), 8 + (12 + 13) + (12 + idat_len) + 12);
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    /* well known crc of an empty IEND chunk */
+    assert_eq!(&png[png.len() - 4..], &[0xae, 0x42, 0x60, 0x82]);
+}
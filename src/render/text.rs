@@ -0,0 +1,124 @@
+//! Stroke font for the HUD.
+//!
+//! Glyphs are polylines on a grid four units wide and six high, with y up.
+//! Letters are drawn in capitals only.
+
+use render::{ Render, ShapeType, Vertex };
+
+/* glyph width plus the gap to the next one, in grid units */
+const ADVANCE: f32 = 6.0;
+const HEIGHT: f32 = 6.0;
+
+type Strokes = &'static [&'static [(u8, u8)]];
+
+fn glyph(c: char) -> Strokes {
+    let c = if c >= 'a' && c <= 'z' { (c as u8 - b'a' + b'A') as char } else { c };
+    match c {
+        '0' => &[&[(0, 0), (4, 0), (4, 6), (0, 6), (0, 0), (4, 6)]],
+        '1' => &[&[(1, 5), (2, 6), (2, 0)], &[(1, 0), (3, 0)]],
+        '2' => &[&[(0, 6), (4, 6), (4, 3), (0, 3), (0, 0), (4, 0)]],
+        '3' => &[&[(0, 6), (4, 6), (4, 0), (0, 0)], &[(1, 3), (4, 3)]],
+        '4' => &[&[(0, 6), (0, 3), (4, 3)], &[(4, 6), (4, 0)]],
+        '5' | 'S' => &[&[(4, 6), (0, 6), (0, 3), (4, 3), (4, 0), (0, 0)]],
+        '6' => &[&[(4, 6), (0, 6), (0, 0), (4, 0), (4, 3), (0, 3)]],
+        '7' => &[&[(0, 6), (4, 6), (4, 0)]],
+        '8' => &[&[(0, 0), (4, 0), (4, 6), (0, 6), (0, 0)], &[(0, 3), (4, 3)]],
+        '9' => &[&[(4, 3), (0, 3), (0, 6), (4, 6), (4, 0), (0, 0)]],
+
+        'A' => &[&[(0, 0), (0, 4), (2, 6), (4, 4), (4, 0)], &[(0, 3), (4, 3)]],
+        'B' => &[&[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)], &[(3, 3), (4, 2), (4, 1), (3, 0), (0, 0)]],
+        'C' => &[&[(4, 6), (0, 6), (0, 0), (4, 0)]],
+        'D' => &[&[(0, 0), (0, 6), (2, 6), (4, 4), (4, 2), (2, 0), (0, 0)]],
+        'E' => &[&[(4, 6), (0, 6), (0, 0), (4, 0)], &[(0, 3), (3, 3)]],
+        'F' => &[&[(4, 6), (0, 6), (0, 0)], &[(0, 3), (3, 3)]],
+        'G' => &[&[(4, 6), (0, 6), (0, 0), (4, 0), (4, 3), (2, 3)]],
+        'H' => &[&[(0, 0), (0, 6)], &[(4, 0), (4, 6)], &[(0, 3), (4, 3)]],
+        'I' => &[&[(1, 6), (3, 6)], &[(2, 6), (2, 0)], &[(1, 0), (3, 0)]],
+        'J' => &[&[(4, 6), (4, 0), (0, 0), (0, 2)]],
+        'K' => &[&[(0, 0), (0, 6)], &[(4, 6), (0, 3), (4, 0)]],
+        'L' => &[&[(0, 6), (0, 0), (4, 0)]],
+        'M' => &[&[(0, 0), (0, 6), (2, 3), (4, 6), (4, 0)]],
+        'N' => &[&[(0, 0), (0, 6), (4, 0), (4, 6)]],
+        'O' => &[&[(0, 0), (4, 0), (4, 6), (0, 6), (0, 0)]],
+        'P' => &[&[(0, 0), (0, 6), (4, 6), (4, 3), (0, 3)]],
+        'Q' => &[&[(0, 0), (4, 0), (4, 6), (0, 6), (0, 0)], &[(2, 2), (4, 0)]],
+        'R' => &[&[(0, 0), (0, 6), (4, 6), (4, 3), (0, 3), (4, 0)]],
+        'T' => &[&[(0, 6), (4, 6)], &[(2, 6), (2, 0)]],
+        'U' => &[&[(0, 6), (0, 0), (4, 0), (4, 6)]],
+        'V' => &[&[(0, 6), (2, 0), (4, 6)]],
+        'W' => &[&[(0, 6), (1, 0), (2, 3), (3, 0), (4, 6)]],
+        'X' => &[&[(0, 0), (4, 6)], &[(0, 6), (4, 0)]],
+        'Y' => &[&[(0, 6), (2, 3), (4, 6)], &[(2, 3), (2, 0)]],
+        'Z' => &[&[(0, 6), (4, 6), (0, 0), (4, 0)]],
+
+        '-' => &[&[(1, 3), (3, 3)]],
+        '+' => &[&[(2, 1), (2, 5)], &[(0, 3), (4, 3)]],
+        '/' => &[&[(0, 0), (4, 6)]],
+        '.' => &[&[(2, 0), (2, 1)]],
+        ':' => &[&[(2, 1), (2, 2)], &[(2, 4), (2, 5)]],
+        '!' => &[&[(2, 6), (2, 2)], &[(2, 1), (2, 0)]],
+        '?' => &[&[(0, 5), (1, 6), (4, 6), (4, 3), (2, 3), (2, 2)], &[(2, 1), (2, 0)]],
+        '<' => &[&[(4, 6), (0, 3), (4, 0)]],
+        '>' => &[&[(0, 6), (4, 3), (0, 0)]],
+        '_' => &[&[(0, 0), (4, 0)]],
+        _ => &[],
+    }
+}
+
+/// Width of the text drawn `size` units high.
+pub fn width(text: &str, size: f32) -> f32 {
+    let n = text.chars().count() as f32;
+    if n == 0.0 {
+        0.0
+    } else {
+        (n * ADVANCE - 2.0) * size / HEIGHT
+    }
+}
+
+/// Draw text with its bottom left corner at `x`, `y`.
+pub fn draw(renderer: &mut Render, text: &str, x: f32, y: f32, size: f32, color: [f32; 4]) {
+    let scale = size / HEIGHT;
+
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i as f32 * ADVANCE * scale;
+        for stroke in glyph(c) {
+            let vertices: Vec<Vertex> = stroke.iter()
+                .map(|&(gx, gy)| Vertex::new([gx as f32 * scale, gy as f32 * scale]))
+                .collect();
+            let mut shape = renderer.create_shape(ShapeType::Game, color, &vertices);
+            shape.set_transform(left, y, 0.0);
+            renderer.draw_shape(&mut shape);
+        }
+    }
+}
+
+/// Draw text centered on `x`.
+pub fn draw_centered(renderer: &mut Render, text: &str, x: f32, y: f32, size: f32, color: [f32; 4]) {
+    draw(renderer, text, x - width(text, size) / 2.0, y, size, color);
+}
+
+#[test]
+fn test_text() {
+    use world::World;
+    use render::soft;
+
+    for c in "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcxyz-+/.:!?<>_".chars() {
+        assert!(glyph(c).len() > 0, "no glyph for {}", c);
+    }
+    assert_eq!(glyph(' ').len(), 0);
+    assert_eq!(width("", 12.0), 0.0);
+    assert_eq!(width("A", 12.0), 8.0);
+    assert_eq!(width("AB", 12.0), 20.0);
+
+    let lit = |text: &str| {
+        let mut r = soft::Renderer::new(&World::new(100.0, 100.0), 100, 100);
+        let clear = r.pixel(0, 0);
+        draw(&mut r, text, -40.0, 0.0, 12.0, [1.0; 4]);
+        (0..100).flat_map(|y| (0..100).map(move |x| (x, y)))
+            .filter(|&(x, y)| r.pixel(x, y) != clear)
+            .count()
+    };
+    assert_eq!(lit(" "), 0);
+    assert!(lit("1") > 10);
+    assert!(lit("88") > lit("8"));
+}
@@ -0,0 +1,469 @@
+use std::io::{ self, Read, Write };
+use std::fs::File;
+use std::mem;
+use std::path::Path;
+
+use game::{ Mode, MODES, MAX_PLAYERS };
+use input::{ Input, Snapshot };
+use timer::MAX_TICK_RATE;
+use world::World;
+
+/* key bits, axes and mouse of one player in one tick */
+type Frame = (u32, [i8; 2], Option<[i16; 2]>);
+/* ticks in a run, and a frame for each player */
+type Run = (u32, Vec<Frame>);
+
+/*
+ * File layout, all integers little endian:
+ *
+ *   magic      "RRPL"
+ *   version    u8
+ *   seed       u32
+ *   tick rate  u32, ticks per second, 1 to `timer::MAX_TICK_RATE`
+ *   world      f32 width, f32 height, both within `world::MIN_SIZE`
+ *              and `world::MAX_SIZE`, u8 flags: 1 wrap x, 2 wrap y,
+ *              4 turret
+ *   players    u8, 1 to `game::MAX_PLAYERS`
+ *   mode       u8, index in `game::MODES`
+ *   ships      u32, `ShipMeta::digest` of the ship class
+ *   waves      u32, `Wave::digest_waves` of the scripted waves
+ *   runs       (varint ticks, then for each player: varint key bits,
+ *              i8 turn, i8 thrust, u8 aim flag, and when it is 1:
+ *              i16 x, i16 y) until the end of file
+ *
+ * Key bits are `Snapshot::bits`, with `Snapshot::again` shifted up by
+ * AGAIN_SHIFT for keys tapped again while they were held. A run of 0
+ * ticks follows ticks left out of the recording, with the keys held at
+ * the last of them. The axes are `Snapshot::analog` and the aim is
+ * `Snapshot::aim_raw`. Files of any other version are refused.
+ */
+const MAGIC: &'static [u8; 4] = b"RRPL";
+const VERSION: u8 = 6;
+const HEADER: usize = 32;
+const AGAIN_SHIFT: u32 = 16;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn push_varint(buf: &mut Vec<u8>, mut v: u32) {
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> io::Result<u32> {
+    let mut v = 0u32;
+    let mut shift = 0;
+    loop {
+        let b = match buf.get(*pos) {
+            Some(b) => *b,
+            None => return Err(invalid("truncated replay")),
+        };
+        *pos += 1;
+
+        if shift > 28 {
+            return Err(invalid("varint too long"));
+        }
+        v |= ((b & 0x7f) as u32) << shift;
+        shift += 7;
+
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> u32 {
+    buf[pos] as u32
+        | (buf[pos + 1] as u32) << 8
+        | (buf[pos + 2] as u32) << 16
+        | (buf[pos + 3] as u32) << 24
+}
+
+fn push_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]);
+}
+
+fn read_f32(buf: &[u8], pos: usize) -> f32 {
+    unsafe { mem::transmute(read_u32(buf, pos)) }
+}
+
+fn push_f32(buf: &mut Vec<u8>, v: f32) {
+    push_u32(buf, unsafe { mem::transmute(v) });
+}
+
+/// Everything besides the input that decides how a recording plays out.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Header {
+    pub seed: u32,
+    /// Ticks per second.
+    pub tick_rate: u32,
+    pub world: World,
+    pub players: usize,
+    pub mode: Mode,
+    /// Whether the first player's gun follows the mouse.
+    pub turret: bool,
+    /// `ShipMeta::digest` of the ship class.
+    pub ships: u32,
+    /// `Wave::digest_waves` of the scripted waves.
+    pub waves: u32,
+}
+
+/// Collects the input state of every tick.
+pub struct Recorder {
+    header: Header,
+    runs: Vec<Run>,
+    /* keys held in the last tick left out, if the one before was */
+    held: Option<Vec<Frame>>,
+}
+
+impl Recorder {
+    pub fn new(header: Header) -> Recorder {
+        Recorder {
+            header: header,
+            runs: Vec::new(),
+            held: None,
+        }
+    }
+
+    /// Add a tick, with a snapshot for each player.
+    pub fn record(&mut self, snapshots: &[Snapshot]) {
+        assert_eq!(snapshots.len(), self.header.players);
+        let frames: Vec<Frame> = snapshots.iter()
+            .map(|s| (s.bits() | s.again() << AGAIN_SHIFT, s.analog(), s.aim_raw()))
+            .collect();
+
+        if let Some(held) = self.held.take() {
+            self.runs.push((0, held));
+        }
+        if let Some(last) = self.runs.last_mut() {
+            if last.0 > 0 && last.1 == frames {
+                last.0 += 1;
+                return;
+            }
+        }
+
+        self.runs.push((1, frames));
+    }
+
+    /// Leave a tick out of the recording. The keys still held when
+    /// recording goes on are kept, so that playback doesn't see them as
+    /// pressed again.
+    pub fn skip(&mut self, snapshots: &[Snapshot]) {
+        assert_eq!(snapshots.len(), self.header.players);
+        self.held = Some(snapshots.iter().map(|s| (s.bits(), s.analog(), s.aim_raw())).collect());
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        let header = &self.header;
+        push_u32(&mut buf, header.seed);
+        push_u32(&mut buf, header.tick_rate);
+        push_f32(&mut buf, header.world.width);
+        push_f32(&mut buf, header.world.height);
+        buf.push(header.world.wrap_x as u8 | (header.world.wrap_y as u8) << 1 | (header.turret as u8) << 2);
+        buf.push(header.players as u8);
+        buf.push(MODES.iter().position(|m| *m == header.mode).unwrap() as u8);
+        push_u32(&mut buf, header.ships);
+        push_u32(&mut buf, header.waves);
+
+        for &(ticks, ref frames) in self.runs.iter() {
+            push_varint(&mut buf, ticks);
+            for &(bits, analog, aim) in frames.iter() {
+                push_varint(&mut buf, bits);
+                buf.push(analog[0] as u8);
+                buf.push(analog[1] as u8);
+                match aim {
+                    Some(aim) => {
+                        buf.push(1);
+                        for v in aim.iter() {
+                            buf.extend_from_slice(&[*v as u8, (*v >> 8) as u8]);
+                        }
+                    }
+                    None => buf.push(0),
+                }
+            }
+        }
+
+        out.write_all(&buf)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = try!(File::create(path));
+        self.write(&mut file)
+    }
+}
+
+/// Plays recorded input back tick by tick.
+pub struct Replay {
+    header: Header,
+    runs: Vec<Run>,
+
+    run: usize,
+    used: u32,
+}
+
+impl Replay {
+    pub fn read<R: Read>(input: &mut R) -> io::Result<Replay> {
+        let mut buf = Vec::new();
+        try!(input.read_to_end(&mut buf));
+
+        if buf.len() < 5 || &buf[..4] != MAGIC {
+            return Err(invalid("not a replay file"));
+        }
+        if buf[4] != VERSION {
+            return Err(invalid("unsupported replay version"));
+        }
+        if buf.len() < HEADER {
+            return Err(invalid("truncated replay"));
+        }
+
+        let header = Header {
+            seed: read_u32(&buf, 5),
+            tick_rate: read_u32(&buf, 9),
+            world: World {
+                width: read_f32(&buf, 13),
+                height: read_f32(&buf, 17),
+                wrap_x: buf[21] & 1 != 0,
+                wrap_y: buf[21] & 2 != 0,
+            },
+            players: buf[22] as usize,
+            mode: match MODES.get(buf[23] as usize) {
+                Some(mode) => *mode,
+                None => return Err(invalid("unknown game mode in replay")),
+            },
+            turret: buf[21] & 4 != 0,
+            ships: read_u32(&buf, 24),
+            waves: read_u32(&buf, 28),
+        };
+        if header.tick_rate < 1 || header.tick_rate > MAX_TICK_RATE {
+            return Err(invalid("bad tick rate in replay"));
+        }
+        if !header.world.is_valid() {
+            return Err(invalid("bad world size in replay"));
+        }
+        if header.players < 1 || header.players > MAX_PLAYERS {
+            return Err(invalid("bad number of players in replay"));
+        }
+
+        let mut pos = HEADER;
+        let mut runs = Vec::new();
+        while pos < buf.len() {
+            let ticks = try!(read_varint(&buf, &mut pos));
+            let mut frames = Vec::new();
+            for _ in 0..header.players {
+                let bits = try!(read_varint(&buf, &mut pos));
+                if pos + 3 > buf.len() || buf[pos + 2] == 1 && pos + 7 > buf.len() {
+                    return Err(invalid("truncated replay"));
+                }
+                let analog = [buf[pos] as i8, buf[pos + 1] as i8];
+                let mut aim = None;
+                if buf[pos + 2] == 1 {
+                    let v = |i: usize| (buf[i] as u16 | (buf[i + 1] as u16) << 8) as i16;
+                    aim = Some([v(pos + 3), v(pos + 5)]);
+                    pos += 4;
+                }
+                pos += 3;
+                frames.push((bits, analog, aim));
+            }
+            runs.push((ticks, frames));
+        }
+
+        Ok(Replay {
+            header: header,
+            runs: runs,
+            run: 0,
+            used: 0,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        let mut file = try!(File::open(path));
+        Replay::read(&mut file)
+    }
+
+    /// What the game was set up with when it was recorded.
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Total number of ticks in the recording.
+    pub fn len(&self) -> u32 {
+        self.runs.iter().map(|r| r.0).sum()
+    }
+
+    /// Set the input of each player to the next recorded tick, false
+    /// when the recording is over.
+    pub fn next(&mut self, inputs: &mut [Input]) -> bool {
+        while let Some(&(ticks, ref frames)) = self.runs.get(self.run) {
+            if self.used < ticks || ticks == 0 {
+                for (input, &(bits, analog, aim)) in inputs.iter_mut().zip(frames.iter()) {
+                    input.set_bits(bits & (1 << AGAIN_SHIFT) - 1, bits >> AGAIN_SHIFT);
+                    input.set_analog(analog);
+                    input.set_aim(aim);
+                    /* keys held through a gap, taken in without a tick */
+                    if ticks == 0 {
+                        input.tick(0.0);
+                    }
+                }
+            }
+            if self.used < ticks {
+                self.used += 1;
+                return true;
+            }
+
+            self.run += 1;
+            self.used = 0;
+        }
+
+        false
+    }
+}
+
+#[test]
+fn test_replay() {
+    use input::{ Axis, Fake, Key };
+    use std::rc::Rc;
+    use game::Game;
+    use ship::ShipMeta;
+    use director::Wave;
+    use world::World;
+
+    let dt = 1.0 / 60.0;
+    let world = World::new(900.5, 500.0);
+    let header = Header {
+        seed: 1234,
+        tick_rate: 60,
+        world: world,
+        players: 2,
+        mode: Mode::Versus,
+        turret: true,
+        ships: ShipMeta::default().digest(),
+        waves: Wave::digest_waves(&[]),
+    };
+    let game = |h: Header| {
+        let mut game = Game::with_players(h.seed, h.world, Rc::new(ShipMeta::default()), Vec::new(), h.players, h.mode);
+        game.set_turret(h.turret);
+        game
+    };
+    let mut first = game(header);
+    let mut inputs = vec![Input::new(), Input::new()];
+    let stick = Fake::new();
+    inputs[0].add_device(Box::new(stick.clone()));
+    let mut rec = Recorder::new(header);
+    let mut ticks = Vec::new();
+
+    for tick in 0..1500 {
+        inputs[0].set(Key::Fire, tick % 200 < 150);
+        inputs[0].set(Key::Forward, tick > 900 && tick < 940);
+        inputs[0].set(Key::Left, tick % 500 > 460);
+        stick.set_axis(Axis::Turn, if tick > 300 && tick < 400 { -0.6 } else { 0.0 });
+        stick.set_axis(Axis::Thrust, if tick > 1200 && tick < 1300 { 0.5 } else { 0.0 });
+        inputs[0].set_aim(if tick > 600 && tick < 700 { Some([-120, 80]) } else { None });
+        inputs[1].set(Key::Right, tick > 100 && tick < 130);
+        inputs[1].set(Key::Fire, tick % 300 < 20);
+
+        /* let go of forward and press it again between two ticks */
+        if tick == 920 {
+            inputs[0].set(Key::Forward, false);
+            inputs[0].set(Key::Forward, true);
+        }
+
+        let snapshots: Vec<_> = inputs.iter_mut().map(|i| i.tick(dt).clone()).collect();
+        rec.record(&snapshots);
+        first.think(dt, &snapshots);
+        ticks.push(snapshots);
+    }
+
+    let mut file = Vec::new();
+    rec.write(&mut file).unwrap();
+    /* mostly constant input compresses to a handful of runs */
+    assert!(file.len() < 400);
+
+    let mut replay = Replay::read(&mut &file[..]).unwrap();
+    assert_eq!(replay.header(), header);
+    assert_eq!(replay.len(), 1500);
+
+    let mut again = game(replay.header());
+    let mut inputs = vec![Input::new(), Input::new()];
+    let mut tick = 0;
+    while replay.next(&mut inputs) {
+        let snapshots: Vec<_> = inputs.iter_mut().map(|i| i.tick(dt).clone()).collect();
+        assert_eq!(snapshots, ticks[tick]);
+        again.think(dt, &snapshots);
+        tick += 1;
+    }
+    assert!(ticks[920][0].just_pressed(Key::Forward));
+
+    for slot in 0..2 {
+        assert_eq!(again.hud.health(slot), first.hud.health(slot));
+        assert_eq!(again.hud.energy(slot), first.hud.energy(slot));
+        assert_eq!(again.score(slot), first.score(slot));
+    }
+    assert_eq!(again.engine.len(), first.engine.len());
+
+    let bodies = |g: &mut Game| -> Vec<_> {
+        g.engine.iter_mut().filter_map(|(_, e)| e.body().map(|b| (b.p, b.a))).collect()
+    };
+    assert!(bodies(&mut again) == bodies(&mut first));
+
+    assert!(Replay::read(&mut &b"nope"[..]).is_err());
+
+    /* a header that can't be played is refused */
+    let broken = |h: Header| {
+        let mut file = Vec::new();
+        Recorder::new(h).write(&mut file).unwrap();
+        Replay::read(&mut &file[..]).is_err()
+    };
+    assert!(broken(Header { tick_rate: 0, ..header }));
+    assert!(broken(Header { world: World::new(0.0, 500.0), ..header }));
+    assert!(broken(Header { world: World::new(::std::f32::NAN, 500.0), ..header }));
+    assert!(!broken(header));
+    file[4] = 5;
+    assert!(Replay::read(&mut &file[..]).is_err());
+}
+
+#[test]
+fn test_gap() {
+    use input::Key;
+
+    let header = Header {
+        seed: 1,
+        tick_rate: 60,
+        world: World::default(),
+        players: 1,
+        mode: Mode::Coop,
+        turret: false,
+        ships: 0,
+        waves: 0,
+    };
+    let dt = 1.0 / 60.0;
+    let mut input = Input::new();
+    let mut rec = Recorder::new(header);
+
+    /* quit goes down while the recording is paused, and stays down */
+    rec.record(&[input.tick(dt).clone()]);
+    input.set(Key::Quit, true);
+    rec.skip(&[input.tick(dt).clone()]);
+    rec.record(&[input.tick(dt).clone()]);
+    rec.record(&[input.tick(dt).clone()]);
+
+    let mut file = Vec::new();
+    rec.write(&mut file).unwrap();
+    let mut replay = Replay::read(&mut &file[..]).unwrap();
+    assert_eq!(replay.len(), 3);
+
+    let mut inputs = [Input::new()];
+    let mut snapshots = Vec::new();
+    while replay.next(&mut inputs) {
+        snapshots.push(inputs[0].tick(dt).clone());
+    }
+    assert_eq!(snapshots.len(), 3);
+    assert!(snapshots[1].pressed(Key::Quit));
+    assert!(!snapshots[1].just_pressed(Key::Quit));
+}
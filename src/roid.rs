@@ -1,52 +1,217 @@
-use rand;
+use std::rc::Rc;
+
 use rand::Rng;
-use cgmath::{ Vector2, vec2 };
+use cgmath::{ Vector2, vec2, EuclideanVector };
+
+use input::Snapshot;
+use entity::{ Entity, State, Random, Spawner };
+use physics::{ self, layer, Body, Contact, Filter, V32 };
+use dust::Dust;
+use hull::Hull;
+use event::Kind;
+use render::{ self, Render };
+use world::World;
+
+/// What a rock is made of.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Material {
+    /* goes into Body::ρ, so mass is density * r^3 */
+    pub density: f32,
+    /* health per unit of radius */
+    pub toughness: f32,
+    /* goes into Body::μ */
+    pub friction: f32,
+    pub color: [f32; 4],
+}
+
+impl Material {
+    pub fn rock() -> Material {
+        Material { density: 1.0, toughness: 1.0 / 20.0, friction: 0.5, color: [1.0; 4] }
+    }
+
+    pub fn ice() -> Material {
+        Material { density: 0.6, toughness: 1.0 / 40.0, friction: 0.05, color: [0.6, 0.8, 1.0, 1.0] }
+    }
 
-use input::Input;
-use entity::{ Entity, State };
-use physics::{ Body };
-use hud::Hud;
-use render;
+    pub fn iron() -> Material {
+        Material { density: 2.5, toughness: 1.0 / 8.0, friction: 0.3, color: [0.8, 0.5, 0.3, 1.0] }
+    }
+}
+
+/// Radius range of one size class, picked with probability proportional
+/// to its weight.
+#[derive(Copy, Clone, Debug)]
+pub struct SizeClass {
+    pub radius: (f32, f32),
+    pub weight: f32,
+}
+
+fn in_range(rng: &mut Random, (lo, hi): (f32, f32)) -> f32 {
+    if hi > lo {
+        rng.gen_range(lo, hi)
+    } else {
+        lo
+    }
+}
+
+fn pick<'a, T, F>(rng: &mut Random, items: &'a [T], weight: F) -> &'a T
+    where F: Fn(&T) -> f32
+{
+    let total = items.iter().fold(0.0, |sum, i| sum + weight(i));
+    let mut x = in_range(rng, (0.0, total));
+    for item in items {
+        if x < weight(item) {
+            return item;
+        }
+        x -= weight(item);
+    }
+    &items[items.len() - 1]
+}
 
-pub struct Generator;
+/// Makes asteroids, one at a time or a whole field of them.
+pub struct Generator {
+    pub sizes: Vec<SizeClass>,
+    pub materials: Vec<(Material, f32)>,
+    /* initial speed and spin, direction and sign are random */
+    pub speed: (f32, f32),
+    pub spin: (f32, f32),
+    /* nothing is placed this close to the point `populate` keeps clear */
+    pub safe_radius: f32,
+    /* whether the smallest fragments leave dust behind */
+    pub dust: bool,
+}
 
 impl Generator {
     pub fn new() -> Generator {
-        Generator
+        Generator {
+            sizes: vec![
+                SizeClass { radius: (40.0, 60.0), weight: 1.0 },
+                SizeClass { radius: (25.0, 40.0), weight: 2.0 },
+                SizeClass { radius: (12.0, 25.0), weight: 2.0 },
+            ],
+            materials: vec![
+                (Material::rock(), 6.0),
+                (Material::ice(), 3.0),
+                (Material::iron(), 1.0),
+            ],
+            speed: (5.0, 30.0),
+            spin: (0.0, 0.2),
+            safe_radius: 150.0,
+            dust: true,
+        }
+    }
+
+    /// Whether there is a size and a material to pick, each list with
+    /// some weight in it.
+    pub fn is_valid(&self) -> bool {
+        let sizes = self.sizes.iter().fold(0.0, |sum, s| sum + s.weight);
+        let materials = self.materials.iter().fold(0.0, |sum, m| sum + m.1);
+        sizes > 0.0 && materials > 0.0 && self.sizes.iter().all(|s| s.radius.0 > 0.0 && s.radius.0 <= s.radius.1)
     }
 
-    pub fn create_at(&self, p: Vector2<f32>) -> Roid {
-        let r = 50.0;
-        let body = Body::init(Body { p: p, r: r, ..Default::default() });
-        Roid::new(body, r)
+    pub fn create_at(&self, rng: &mut Random, p: Vector2<f32>) -> Roid {
+        use std::f32::consts::PI;
+
+        assert!(self.is_valid(), "asteroid generator without sizes or materials to pick from");
+
+        let size = pick(rng, &self.sizes, |s| s.weight).radius;
+        let material = pick(rng, &self.materials, |m| m.1).0;
+
+        let r = in_range(rng, size);
+        let dir = in_range(rng, (0.0, 2.0 * PI));
+        let dp = vec2(dir.cos(), dir.sin()) * in_range(rng, self.speed);
+        let da = in_range(rng, self.spin) * if rng.gen() { 1.0 } else { -1.0 };
+
+        let body = Body::init(Body {
+            p: p,
+            dp: dp,
+            da: da,
+            r: r,
+            ρ: material.density,
+            μ: material.friction,
+            filter: Filter::new(layer::ASTEROID, layer::ALL),
+            ..Default::default()
+        });
+        Roid::new(rng, body, material, self.dust)
+    }
+
+    /// Scatter `count` asteroids over the world, keeping `safe_radius`
+    /// around `clear` empty.
+    pub fn populate(&self, rng: &mut Random, world: &World, clear: Vector2<f32>, count: usize) -> Vec<Roid> {
+        let mut roids: Vec<Roid> = Vec::new();
+        let (lo, hi) = (world.lo(), world.hi());
+
+        for _ in 0..count {
+            let mut roid = self.create_at(rng, vec2(0.0, 0.0));
+            let r = roid.body.r;
+
+            /* give up on a crowded spot after a while, rather than loop forever */
+            for _ in 0..100 {
+                let p = vec2(in_range(rng, (lo.x + r, hi.x - r)), in_range(rng, (lo.y + r, hi.y - r)));
+                let clear_of_ship = world.distance(p, clear) > self.safe_radius + r;
+                let clear_of_rocks = roids.iter().all(|o| world.distance(p, o.body.p) > o.body.r + r);
+
+                if clear_of_ship && clear_of_rocks {
+                    roid.body.p = p;
+                    roid.body.pp = p;
+                    roids.push(roid);
+                    break;
+                }
+            }
+        }
+
+        roids
     }
 }
 
+/* fragments smaller than this turn to dust */
+const MIN_RADIUS: f32 = 10.0;
+/* bounds on how fast fragments fly apart */
+const KICK_SPEED: (f32, f32) = (5.0, 60.0);
+
+/* where and how hard an asteroid was last hit */
+#[derive(Copy, Clone, Debug)]
+struct Hit {
+    /* contact point relative to the centre */
+    offset: V32,
+    energy: f32,
+}
+
 pub struct Roid
 {
     body: Body,
     state: State,
     shape: Option<render::Shape>,
+    outline: Vec<[f32; 2]>,
+    material: Material,
+    dust: bool,
 
     health: f32,
+    hit: Option<Hit>,
 }
 
 impl Roid {
-    fn new(body: Body, size: f32) -> Roid {
+    fn new(rng: &mut Random, mut body: Body, material: Material, dust: bool) -> Roid {
+        let outline = Roid::make_outline(rng, body.r);
+        let health = body.r * material.toughness;
+        body.hull = Some(Rc::new(Hull::from_polar(&outline)));
         Roid {
             body: body,
             state: State::Alive,
             shape: None,
+            outline: outline,
+            material: material,
+            dust: dust,
 
-            health: size / 20.0,
+            health: health,
+            hit: None,
         }
     }
 
-    fn make_shape(&self, renderer: &mut render::Renderer) -> render::Shape {
-        let mut rng = rand::thread_rng();
-
-        let size = self.body.r;
-        let n = rng.gen_range(size as u32 / 2, size as u32);
+    fn make_outline(rng: &mut Random, size: f32) -> Vec<[f32; 2]> {
+        /* at least a triangle, however small */
+        let lo = (size as u32 / 2).max(3);
+        let n = rng.gen_range(lo, (size as u32).max(lo + 1));
 
         let mut p = Vec::new();
         for _ in 0..n {
@@ -57,29 +222,94 @@ impl Roid {
         let p0 = p[0].clone();
         p.push(p0);
 
-        renderer.create_shape_simple([1.0; 4], &p[..])
+        p
     }
 
-    fn explode(&mut self, spawn: &mut Vec<Box<Entity>>) {
+    fn make_shape(&self, renderer: &mut Render) -> render::Shape {
+        renderer.create_shape_simple(self.material.color, &self.outline[..])
+    }
+
+    /// Bodies of the pieces this asteroid breaks into.
+    ///
+    /// The pieces share out the mass unevenly and keep the centre of mass
+    /// and the momentum of the whole. They fly apart away from the point of
+    /// the last hit, faster the harder that hit was.
+    fn fragments(&self, rng: &mut Random) -> Vec<Body> {
         use std::f32::consts::PI;
 
-        if self.body.r <= 20.0 {
-            return;
+        let body = &self.body;
+        let hit = self.hit.unwrap_or(Hit { offset: vec2(0.0, 0.0), energy: 0.0 });
+
+        let n = rng.gen_range(3, 6);
+        let weights: Vec<f32> = (0..n).map(|_| rng.gen_range(0.5, 1.5)).collect();
+        let total = weights.iter().fold(0.0, |sum, w| sum + w);
+        let masses: Vec<f32> = weights.iter().map(|w| body.m * w / total).collect();
+        let radii: Vec<f32> = masses.iter().map(|m| (m / body.ρ).powf(1.0 / 3.0)).collect();
+
+        /* spread around the centre, starting from the side that was hit */
+        let step = 2.0 * PI / n as f32;
+        let start = hit.offset.y.atan2(hit.offset.x);
+        let angles: Vec<f32> = (0..n)
+            .map(|i| start + i as f32 * step + rng.gen_range(-step / 4.0, step / 4.0))
+            .collect();
+
+        /* far enough out that neighbours don't overlap */
+        let mut ring: f32 = 0.0;
+        for i in 0..n {
+            let j = (i + 1) % n;
+            let gap = if j == 0 { angles[0] + 2.0 * PI - angles[i] } else { angles[j] - angles[i] };
+            ring = ring.max((radii[i] + radii[j]) / (2.0 * (gap / 2.0).sin()));
         }
 
-        let mut rng = rand::thread_rng();
-        let pieces = rng.gen_range(3, 6);
-        let angle = PI / pieces as f32;
-        for p in 0..pieces {
-            let a = (p as f32) * 2.0 * angle + rng.gen_range(-angle/2.0, angle/2.0);
-            let d = vec2(a.cos(), a.sin());
-            let p = self.body.p + d * self.body.r;
-            let r = self.body.r / 2.0;
-            let dp = self.body.dp + d;
-            let da = rng.gen_range(-0.1, 0.1);
-            let body = Body::init(Body { p: p, dp: dp, da: da, r: r, ..Default::default() });
-            let roid = Roid::new(body, r);
-            spawn.push(Box::new(roid));
+        let mut offsets: Vec<V32> = angles.iter().map(|a| vec2(a.cos(), a.sin()) * ring).collect();
+        let mut kicks: Vec<V32> = offsets.iter().map(|&o| {
+            let d = o - hit.offset;
+            if d.length2() > 0.0 { d.normalize() } else { o.normalize() }
+        }).collect();
+
+        /* take out the mass weighted means, so the centre of mass stays put
+         * and the kicks add up to no momentum */
+        let mean = |v: &Vec<V32>| {
+            v.iter().zip(masses.iter())
+                .fold(vec2(0.0, 0.0), |sum, (&v, &m)| sum + v * m) / body.m
+        };
+        let (mo, mk) = (mean(&offsets), mean(&kicks));
+        for i in 0..n {
+            offsets[i] = offsets[i] - mo;
+            kicks[i] = kicks[i] - mk;
+        }
+
+        /* put a share of the hit into the fragments, as kinetic energy
+         * measured the same way physics::collide does */
+        let sum = kicks.iter().zip(masses.iter()).fold(0.0, |sum, (k, m)| sum + k.length2() * m);
+        let speed = (hit.energy * physics::UNIT_OF_ENERGY / body.m).sqrt()
+            .max(KICK_SPEED.0).min(KICK_SPEED.1);
+        let scale = if sum > 0.0 { speed * (body.m / sum).sqrt() } else { 0.0 };
+
+        (0..n).map(|i| {
+            let mut piece = Body::init(Body {
+                p: body.p + offsets[i],
+                dp: body.dp + kicks[i] * scale,
+                a: body.a,
+                da: body.da + rng.gen_range(-0.1, 0.1),
+                r: radii[i],
+                ρ: body.ρ,
+                μ: body.μ,
+                filter: body.filter,
+                ..Default::default()
+            });
+            piece.m = masses[i];
+            piece
+        }).collect()
+    }
+
+    fn explode(&mut self, rng: &mut Random, spawn: &mut Spawner) {
+        for body in self.fragments(rng) {
+            if body.r >= MIN_RADIUS {
+                spawn.push(Box::new(Roid::new(rng, body, self.material, self.dust)));
+            } else if self.dust {
+                spawn.push(Box::new(Dust::new(rng, body)));
+            }
         }
     }
 
@@ -89,32 +319,126 @@ impl Roid {
 }
 
 impl Entity for Roid {
-    fn draw(&mut self, renderer: &mut render::Renderer) {
+    fn draw(&mut self, renderer: &mut Render, world: &World, alpha: f32) {
         if self.shape.is_none() {
             self.shape = Some(self.make_shape(renderer));
         }
         if let Some(shape) = self.shape.as_mut() {
-            shape.set_transform(self.body.p.x, self.body.p.y, self.body.a);
-            renderer.draw_shape(shape);
+            let a = self.body.lerp_a(alpha);
+            for p in world.images(self.body.lerp_p(alpha), self.body.r * 1.2) {
+                shape.set_transform(p.x, p.y, a);
+                renderer.draw_shape(shape);
+            }
         }
     }
 
-    fn think(&mut self, dt: f32, _input: &Input, _hud: &mut Hud, spawn: &mut Vec<Box<Entity>>) -> State {
-        self.body.think(dt);
+    fn think(&mut self, dt: f32, _: &[Snapshot], world: &World, rng: &mut Random, spawn: &mut Spawner) -> State {
+        self.body.think(dt, world);
 
         if self.health <= 0.0 {
             self.state = State::Dead;
-            self.explode(spawn);
+            self.explode(rng, spawn);
         }
 
         self.state
     }
 
-    fn collide(&mut self, _other: &mut Entity, energy: f32) {
-        self.damage(energy);
+    fn collide(&mut self, _other: &mut Entity, contact: &Contact) {
+        self.damage(contact.energy);
+        self.hit = Some(Hit {
+            offset: -contact.normal * self.body.r,
+            energy: contact.energy,
+        });
     }
 
     fn body(&mut self) -> Option<&mut Body> {
         Some(&mut self.body)
     }
+
+    fn kind(&self) -> Kind {
+        Kind::Asteroid
+    }
+
+    fn health(&self) -> Option<f32> {
+        Some(self.health)
+    }
+}
+
+#[test]
+fn test_populate() {
+    use entity::seeded;
+
+    let mut rng = seeded(3);
+    let world = World::new(1000.0, 600.0);
+    let gen = Generator::new();
+    let ship = vec2(-100.0, 0.0);
+
+    let roids = gen.populate(&mut rng, &world, ship, 12);
+    assert_eq!(roids.len(), 12);
+
+    for (i, a) in roids.iter().enumerate() {
+        assert!(world.distance(a.body.p, ship) > gen.safe_radius + a.body.r);
+        assert!(a.body.p.x.abs() <= 500.0 - a.body.r && a.body.p.y.abs() <= 300.0 - a.body.r);
+        assert!(a.body.r >= 12.0 && a.body.r <= 60.0);
+        assert_eq!(a.body.m, a.material.density * a.body.r.powi(3));
+        for b in roids[..i].iter() {
+            assert!(world.distance(a.body.p, b.body.p) > a.body.r + b.body.r);
+        }
+    }
+
+    let iron = Generator {
+        sizes: vec![ SizeClass { radius: (30.0, 30.0), weight: 1.0 } ],
+        materials: vec![ (Material::iron(), 1.0) ],
+        speed: (0.0, 0.0),
+        ..Generator::new()
+    };
+    let roid = iron.create_at(&mut rng, ship);
+    assert_eq!(roid.body.ρ, 2.5);
+    assert_eq!(roid.health, 30.0 / 8.0);
+    assert_eq!(roid.body.dp, vec2(0.0, 0.0));
+
+    /* tiny rocks still get an outline, empty lists are caught */
+    let tiny = Generator { sizes: vec![ SizeClass { radius: (0.5, 1.5), weight: 1.0 } ], ..iron };
+    for _ in 0..20 {
+        assert!(tiny.create_at(&mut rng, ship).outline.len() >= 4);
+    }
+    assert!(tiny.is_valid());
+    assert!(!Generator { sizes: Vec::new(), ..Generator::new() }.is_valid());
+    assert!(!Generator { materials: Vec::new(), ..Generator::new() }.is_valid());
+}
+
+#[test]
+fn test_fragments() {
+    use entity::seeded;
+
+    let mut rng = seeded(11);
+    let gen = Generator {
+        sizes: vec![ SizeClass { radius: (50.0, 50.0), weight: 1.0 } ],
+        ..Generator::new()
+    };
+
+    for &energy in &[0.0, 0.05, 5.0] {
+        let mut roid = gen.create_at(&mut rng, vec2(10.0, -20.0));
+        roid.hit = Some(Hit { offset: vec2(-50.0, 0.0), energy: energy });
+
+        let body = &roid.body;
+        let pieces = roid.fragments(&mut rng);
+        assert!(pieces.len() >= 3 && pieces.len() <= 5);
+
+        let m = pieces.iter().fold(0.0, |sum, b| sum + b.m);
+        let c = pieces.iter().fold(vec2(0.0, 0.0), |sum, b| sum + b.p * b.m) / m;
+        let p = pieces.iter().fold(vec2(0.0, 0.0), |sum, b| sum + b.dp * b.m);
+
+        assert!((m - body.m).abs() < body.m * 1e-4);
+        assert!((c - body.p).length() < 1e-2);
+        assert!((p - body.dp * body.m).length() < body.m * 1e-4);
+
+        /* pieces drift apart, and don't start out overlapping */
+        for (i, a) in pieces.iter().enumerate() {
+            assert!((a.dp - body.dp).length() >= KICK_SPEED.0 * 0.1);
+            for b in pieces[..i].iter() {
+                assert!((a.p - b.p).length() >= a.r + b.r - 1e-3);
+            }
+        }
+    }
 }
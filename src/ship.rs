@@ -1,12 +1,17 @@
+use std::f32::consts::PI;
 use std::rc::Rc;
 
 use cgmath::{ Vector2, vec2 };
-use input::{ Key, Input };
-use physics::Body;
-use entity::{ Entity, State };
-use render;
-use hud::Hud;
+use rand::Rng;
+use input::{ Axis, Key, Snapshot };
+use physics::{ layer, Body, Contact, Filter };
+use entity::{ Entity, State, Random, Spawner };
+use render::{ self, Render };
+use event::Kind;
 use beam::Beam;
+use hull::Hull;
+use world::World;
+use config::{ self, Section };
 
 pub struct ShipMeta {
     body_radius: f32,
@@ -60,6 +65,98 @@ impl Default for ShipMeta {
     }
 }
 
+/* field name, minimum, maximum */
+const META_FIELDS: &'static [(&'static str, f64, f64)] = &[
+    ("body_radius", 1.0, 200.0),
+    ("init_score", 0.0, 1e9),
+    ("init_power", 0.0, 1e9),
+    ("max_health", 0.1, 1e6),
+    ("max_energy", 0.1, 1e6),
+    ("linear_thrust", 0.0, 1e9),
+    /* above zero, `consume` divides by the energy asked for */
+    ("linear_power", 0.001, 1e6),
+    ("angular_thrust", 0.0, 1e9),
+    ("angular_power", 0.001, 1e6),
+    ("angular_limit", 0.0, 100.0),
+    ("angular_damage", 0.0, 1e3),
+    ("fire_delay", 0.0, 60.0),
+    ("beam_spread", 0.0, 2.0),
+    ("beam_speed", 1.0, 1e5),
+    ("beam_radius", 0.1, 100.0),
+];
+
+impl ShipMeta {
+    /// Read one ship class, fields not mentioned keep their default values.
+    pub fn from_section(section: &Section) -> Result<ShipMeta, config::Error> {
+        let mut meta = ShipMeta::default();
+        let names: Vec<_> = META_FIELDS.iter().map(|f| f.0).collect();
+
+        for entry in section.entries.iter() {
+            let &(_, lo, hi) = match META_FIELDS.iter().find(|f| f.0 == entry.key) {
+                Some(field) => field,
+                None => return Err(entry.unknown(&section.name, &names)),
+            };
+
+            let v = try!(entry.num_in(lo, hi));
+            match &entry.key[..] {
+                "body_radius" => meta.body_radius = v as f32,
+                "init_score" => meta.init_score = v as u32,
+                "init_power" => meta.init_power = v as u32,
+                "max_health" => meta.max_health = v as f32,
+                "max_energy" => meta.max_energy = v as f32,
+                "linear_thrust" => meta.linear_thrust = v as f32,
+                "linear_power" => meta.linear_power = v as f32,
+                "angular_thrust" => meta.angular_thrust = v as f32,
+                "angular_power" => meta.angular_power = v as f32,
+                "angular_limit" => meta.angular_limit = v as f32,
+                "angular_damage" => meta.angular_damage = v as f32,
+                "fire_delay" => meta.fire_delay = v as f32,
+                "beam_spread" => meta.beam_spread = v as f32,
+                "beam_speed" => meta.beam_speed = v as f32,
+                "beam_radius" => meta.beam_radius = v as f32,
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(meta)
+    }
+
+    /// Fingerprint of every field, for replays to check they play with
+    /// the class they were recorded with.
+    pub fn digest(&self) -> u32 {
+        config::digest(&[
+            self.body_radius as f64,
+            self.init_score as f64,
+            self.init_power as f64,
+            self.max_health as f64,
+            self.max_energy as f64,
+            self.linear_thrust as f64,
+            self.linear_power as f64,
+            self.angular_thrust as f64,
+            self.angular_power as f64,
+            self.angular_limit as f64,
+            self.angular_damage as f64,
+            self.fire_delay as f64,
+            self.beam_spread as f64,
+            self.beam_speed as f64,
+            self.beam_radius as f64,
+        ])
+    }
+
+    /// Read every ship class in a data file, one class per section.
+    pub fn load_classes(sections: &[Section]) -> Result<Vec<(String, ShipMeta)>, config::Error> {
+        let mut classes = Vec::new();
+        for section in sections {
+            if section.name.is_empty() {
+                let line = section.entries[0].line;
+                return Err(config::Error::new(line, "ship fields must be inside a [class] section".to_owned()));
+            }
+            classes.push((section.name.clone(), try!(ShipMeta::from_section(section))));
+        }
+        Ok(classes)
+    }
+}
+
 pub struct Ship
 {
     pub body: Body,
@@ -71,19 +168,34 @@ pub struct Ship
     pub energy: f32,
 
     fire_delay: f32,
+    /* seconds left of not taking damage */
+    shield: f32,
+    /* world angle of the mouse aimed turret, if the ship has one */
+    turret: Option<f32>,
+    /* player slot whose input flies the ship, and whether its shots
+     * hit other ships */
+    player: usize,
+    friendly_fire: bool,
 
     meta: Rc<ShipMeta>,
     shape: Option<render::Shape>,
+    turret_shape: Option<render::Shape>,
+    outline: Vec<[f32; 2]>,
 }
 
 impl Ship
 {
     pub fn new(p: Vector2<f32>, meta: Rc<ShipMeta>) -> Ship
     {
+        let scale = meta.body_radius / SHIP_SIZE;
+        let outline: Vec<[f32; 2]> = SHIP_SHAPE.iter().map(|v| [v[0], v[1] * scale]).collect();
+
         Ship {
             body: Body::init(Body {
                 p: p,
                 r: meta.body_radius,
+                hull: Some(Rc::new(Hull::from_polar(&outline))),
+                filter: Filter::new(layer::SHIP, layer::ALL),
                 ..Default::default()
             }),
             state: State::Alive,
@@ -94,12 +206,68 @@ impl Ship
             energy: meta.max_energy,
 
             fire_delay: 0.0,
+            shield: 0.0,
+            turret: None,
+            player: 0,
+            friendly_fire: true,
 
             meta: meta,
             shape: None,
+            turret_shape: None,
+            outline: outline,
         }
     }
 
+    /// Make the ship immune to damage for a while, as after a respawn.
+    pub fn shielded(self, time: f32) -> Ship {
+        Ship {
+            shield: time,
+            ..self
+        }
+    }
+
+    pub fn is_shielded(&self) -> bool {
+        self.shield > 0.0
+    }
+
+    /// Fly with the input of player slot `player`.
+    pub fn for_player(self, player: usize) -> Ship {
+        Ship {
+            player: player,
+            ..self
+        }
+    }
+
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    /// Whether shots hit other ships, or fly through them.
+    pub fn set_friendly_fire(&mut self, on: bool) {
+        self.friendly_fire = on;
+    }
+
+    /// Shoot from a turret that follows the mouse instead of straight
+    /// ahead, the keys still fly the hull.
+    pub fn set_turret(&mut self, on: bool) {
+        self.turret = if on { Some(self.body.a) } else { None };
+    }
+
+    /// Where the turret points, if there is one.
+    pub fn turret(&self) -> Option<f32> {
+        self.turret
+    }
+
+    /// Energy left, from 0 to 1.
+    pub fn energy_level(&self) -> f32 {
+        self.energy / self.meta.max_energy
+    }
+
+    /// Health left, from 0 to 1.
+    pub fn health_level(&self) -> f32 {
+        self.health / self.meta.max_health
+    }
+
     fn consume(&mut self, energy: f32) -> f32 {
         let output = if energy > self.energy {
             let avail = self.energy;
@@ -113,19 +281,29 @@ impl Ship
         output / energy
     }
 
-    fn accel(&mut self, dt: f32, dir: f32) {
-        let energy = self.meta.linear_power * dt;
-        let thrust = self.meta.linear_thrust * self.consume(energy) * dt;
+    /* thrust towards `dir` with `amount` of full power, from 0 to 1 */
+    fn accel(&mut self, dt: f32, dir: f32, amount: f32) {
+        let energy = self.meta.linear_power * dt * amount;
+        let thrust = self.meta.linear_thrust * self.consume(energy) * dt * amount;
         self.body.apply_force_local(thrust, dir);
     }
 
-    fn turn(&mut self, dt: f32, dir: f32) {
-        let energy = self.meta.angular_power * dt;
+    /* turn left with `amount` of full power, negative turns right */
+    fn turn(&mut self, dt: f32, amount: f32) {
+        let energy = self.meta.angular_power * dt * amount.abs();
         let torque = self.meta.angular_thrust * self.consume(energy) * dt;
-        self.body.apply_torque(torque * dir);
+        self.body.apply_torque(torque * amount);
     }
 
-    fn fire(&mut self, dt: f32, spawn: &mut Vec<Box<Entity>>) {
+    /* point the turret at `aim` */
+    fn aim(&mut self, aim: Vector2<f32>, world: &World) {
+        let d = world.delta(aim, self.body.p);
+        if self.turret.is_some() && (d.x != 0.0 || d.y != 0.0) {
+            self.turret = Some(d.y.atan2(d.x) / PI);
+        }
+    }
+
+    fn fire(&mut self, rng: &mut Random, spawn: &mut Spawner) {
         let meta = &self.meta;
 
         if self.fire_delay >= 0.0 {
@@ -134,20 +312,39 @@ impl Ship
 
         self.fire_delay = meta.fire_delay;
 
-        let fwd = self.body.to_world(vec2(1.0, 0.0));
+        /* the turret scatters its shots, the hull gun does not */
+        let (fwd, a) = match self.turret {
+            Some(a) => {
+                let a = if meta.beam_spread > 0.0 {
+                    a + rng.gen_range(-0.5, 0.5) * meta.beam_spread
+                } else {
+                    a
+                };
+                (vec2((a * PI).cos(), (a * PI).sin()), a)
+            }
+            None => (self.body.to_world(vec2(1.0, 0.0)), self.body.a),
+        };
         let ofs = self.body.r + meta.beam_radius + 2.5;
         let body = Body::init(Body {
             p: self.body.p + fwd * ofs,
             dp: self.body.dp + fwd * meta.beam_speed,
-            a: self.body.a,
+            a: a,
             r: meta.beam_radius,
+            owner: self.body.id,
             ..Default::default()
         });
         let beam = Beam::new(body);
-        spawn.push(Box::new(beam));
+        if self.friendly_fire {
+            spawn.push(Box::new(beam));
+        } else {
+            spawn.push(Box::new(beam.sparing_ships()));
+        }
     }
 
     fn damage(&mut self, damage: f32) {
+        if self.shield > 0.0 {
+            return;
+        }
         if damage >= self.health {
             self.health = 0.0;
             self.state = State::Dead;
@@ -159,39 +356,67 @@ impl Ship
 
 impl Entity for Ship
 {
-    fn draw(&mut self, renderer: &mut render::Renderer) {
+    fn draw(&mut self, renderer: &mut Render, world: &World, alpha: f32) {
         if self.shape.is_none() {
-            self.shape = Some(renderer.create_shape_simple([1.0; 4], SHIP_SHAPE));
+            self.shape = Some(renderer.create_shape_simple(render::player_color(self.player), &self.outline));
+        }
+
+        /* blink while shielded */
+        if self.shield > 0.0 && (self.shield * 8.0) as i32 % 2 == 1 {
+            return;
         }
 
         if let Some(shape) = self.shape.as_mut() {
-            shape.set_transform(self.body.p.x, self.body.p.y, self.body.a);
-            renderer.draw_shape(shape);
+            let a = self.body.lerp_a(alpha);
+            for p in world.images(self.body.lerp_p(alpha), self.body.r) {
+                shape.set_transform(p.x, p.y, a);
+                renderer.draw_shape(shape);
+            }
+        }
+
+        if let Some(a) = self.turret {
+            if self.turret_shape.is_none() {
+                let r = self.meta.body_radius;
+                let barrel = [[0.0, r * 1.3], [0.5, r * 0.2], [1.5, r * 0.2], [0.0, r * 1.3]];
+                self.turret_shape = Some(renderer.create_shape_simple([1.0, 0.8, 0.3, 1.0], &barrel));
+            }
+            let shape = self.turret_shape.as_mut().unwrap();
+            for p in world.images(self.body.lerp_p(alpha), self.body.r) {
+                shape.set_transform(p.x, p.y, a);
+                renderer.draw_shape(shape);
+            }
         }
     }
 
-    fn think(&mut self, dt: f32, input: &Input, hud: &mut Hud, spawn: &mut Vec<Box<Entity>>) -> State {
-        if input.pressed(Key::Forward) {
-            self.accel(dt, 0.0);
+    fn think(&mut self, dt: f32, inputs: &[Snapshot], world: &World, rng: &mut Random, spawn: &mut Spawner) -> State {
+        /* a slot nobody plays leaves the ship drifting */
+        let idle = Snapshot::new();
+        let input = inputs.get(self.player).unwrap_or(&idle);
+
+        let thrust = input.axis(Axis::Thrust);
+        if thrust > 0.0 {
+            self.accel(dt, 0.0, thrust);
         }
         if input.pressed(Key::Reverse) {
-            self.accel(dt, 1.0);
-        }
-        if input.pressed(Key::Left) {
-            self.turn(dt, 1.0);
+            self.accel(dt, 1.0, 1.0);
         }
-        if input.pressed(Key::Right) {
-            self.turn(dt, -1.0);
+        let turn = input.axis(Axis::Turn);
+        if turn != 0.0 {
+            self.turn(dt, turn);
         }
 
-        self.body.think(dt);
+        self.body.think(dt, world);
 
+        if let Some(aim) = input.aim() {
+            self.aim(aim, world);
+        }
         if input.pressed(Key::Fire) {
-            self.fire(dt, spawn);
+            self.fire(rng, spawn);
         }
 
 
         self.fire_delay -= dt;
+        self.shield = (self.shield - dt).max(0.0);
 
         let over = self.body.da.abs() - self.meta.angular_limit;
         if over > 0.0 {
@@ -199,21 +424,32 @@ impl Entity for Ship
             self.damage(damage);
         }
 
-        hud.update(self.energy / self.meta.max_energy,
-                   self.health / self.meta.max_health);
+        if self.health <= 0.0 {
+            self.state = State::Dead;
+        }
 
         self.state
     }
 
-    fn collide(&mut self, _other: &mut Entity, energy: f32) {
-        self.damage(energy);
+    fn collide(&mut self, _other: &mut Entity, contact: &Contact) {
+        self.damage(contact.energy);
     }
 
     fn body(&mut self) -> Option<&mut Body> {
         Some(&mut self.body)
     }
+
+    fn kind(&self) -> Kind {
+        Kind::Ship
+    }
+
+    fn health(&self) -> Option<f32> {
+        Some(self.health)
+    }
 }
 
+/* outline for a ship of radius SHIP_SIZE, scaled to fit the class */
+const SHIP_SIZE: f32 = 20.0;
 const SHIP_SHAPE: &'static [[f32; 2]] = &[
     [0.05, 15.0],
     [0.6, 5.0],
@@ -227,3 +463,103 @@ const SHIP_SHAPE: &'static [[f32; 2]] = &[
     [1.95, 15.0],
     [0.05, 15.0],
 ];
+
+#[test]
+fn test_meta_from_file() {
+    let sections = config::parse("
+        [scout]
+        body_radius = 12
+        linear_thrust = 2.4e6
+        fire_delay = 0.05
+
+        [tank]
+        max_health = 40
+    ").unwrap();
+
+    let classes = ShipMeta::load_classes(&sections).unwrap();
+    assert_eq!(classes.len(), 2);
+    assert_eq!(classes[0].0, "scout");
+    assert_eq!(classes[0].1.body_radius, 12.0);
+    assert_eq!(classes[0].1.fire_delay, 0.05);
+    assert_eq!(classes[0].1.max_health, ShipMeta::default().max_health);
+    assert_eq!(classes[1].1.max_health, 40.0);
+    assert!(classes[0].1.digest() != classes[1].1.digest());
+    assert_eq!(ShipMeta::default().digest(), ShipMeta::default().digest());
+
+    let err = |text| ShipMeta::load_classes(&config::parse(text).unwrap()).err().unwrap().to_string();
+
+    assert_eq!(err("[a]\nthrust = 1"),
+               "line 2: unknown field `thrust` in [a], did you mean `linear_thrust`?");
+    assert_eq!(err("[a]\nbeam_spread = 3"),
+               "line 2: `beam_spread` is 3, should be between 0 and 2");
+    assert_eq!(err("[a]\nangular_power = 0"),
+               "line 2: `angular_power` is 0, should be between 0.001 and 1000000");
+    assert_eq!(err("[a]\nfire_delay = \"fast\""),
+               "line 2: `fire_delay` should be a number, not a string");
+    assert_eq!(err("fire_delay = 1"),
+               "line 1: ship fields must be inside a [class] section");
+}
+
+#[test]
+fn test_analog_turn() {
+    use input::{ Input, Fake };
+    use entity::Engine;
+
+    /* spin after a second of turning, and energy left */
+    fn turn(v: f32) -> (f32, f32) {
+        let mut engine = Engine::new(1, World::default());
+        let ship = engine.add(Box::new(Ship::new(vec2(0.0, 0.0), Rc::new(ShipMeta::default()))));
+        let mut input = Input::new();
+        let pad = Fake::new();
+        input.add_device(Box::new(pad.clone()));
+        input.set_deadzone(0.0);
+        pad.set_axis(Axis::Turn, v);
+
+        for _ in 0..60 {
+            engine.think(1.0 / 60.0, &[input.tick(1.0 / 60.0).clone()]);
+        }
+        let ship = engine.get_as::<Ship>(ship).unwrap();
+        (ship.body.da, ship.energy)
+    }
+
+    let (full, full_energy) = turn(1.0);
+    let (half, half_energy) = turn(0.5);
+    let (right, _) = turn(-0.5);
+
+    assert!(full > 0.0);
+    assert!((half / full - 0.5).abs() < 0.01);
+    assert_eq!(right, -half);
+    assert!(half_energy > full_energy);
+}
+
+#[test]
+fn test_turret() {
+    use input::Input;
+    use entity::Engine;
+    use beam::Beam;
+
+    let mut engine = Engine::new(1, World::default());
+    let mut ship = Ship::new(vec2(0.0, 0.0), Rc::new(ShipMeta::default()));
+    ship.set_turret(true);
+    let ship = engine.add(Box::new(ship));
+    let mut input = Input::new();
+
+    /* aim straight down while the hull faces right, and fire a while */
+    input.set_aim(Some([0, -100]));
+    input.set(Key::Fire, true);
+    for _ in 0..30 {
+        engine.think(1.0 / 60.0, &[input.tick(1.0 / 60.0).clone()]);
+    }
+
+    assert_eq!(engine.get_as::<Ship>(ship).unwrap().turret(), Some(-0.5));
+    assert_eq!(engine.get_as::<Ship>(ship).unwrap().body.a, 0.0);
+
+    /* every shot goes down, scattered within the spread */
+    let spread = ShipMeta::default().beam_spread;
+    let angles: Vec<f32> = engine.iter_of::<Beam>()
+        .map(|(_, b)| b.body().map(|b| b.dp.y.atan2(b.dp.x) / PI).unwrap())
+        .collect();
+    assert!(angles.len() > 2);
+    assert!(angles.iter().all(|a| (a + 0.5).abs() <= spread / 2.0));
+    assert!(angles.iter().any(|a| *a != angles[0]));
+}
@@ -1,31 +1,79 @@
 use time;
 
+/// Fastest tick rate the game runs at.
+pub const MAX_TICK_RATE: u32 = 1000;
+
+/// Fixed timestep clock.
+///
+/// Real time is accumulated and handed out in whole ticks of `step`
+/// seconds, whatever is left over is used to interpolate rendering.
 pub struct Timer {
     last: f64,
-    next: f64,
-    dt_min: f64,
-    dt_max: f64,
+    acc: f64,
+    step: f64,
+    max_steps: u32,
 }
 
 impl Timer {
-    pub fn new(fps_min: f64, fps_max: f64) -> Timer {
+    pub fn new(tick_rate: f64, max_steps: u32) -> Timer {
         Timer {
             last: 0.0,
-            next: 0.0,
-            dt_min: 1.0 / fps_max,
-            dt_max: 1.0 / fps_min,
+            acc: 0.0,
+            step: 1.0 / tick_rate,
+            max_steps: max_steps,
         }
     }
 
-    pub fn are_we_yet(&mut self) -> Option<f64> {
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    pub fn reset(&mut self) {
+        self.last = time::precise_time_s();
+        self.acc = 0.0;
+    }
+
+    /// Number of ticks to simulate to catch up with the wall clock.
+    pub fn ticks(&mut self) -> u32 {
         let time = time::precise_time_s();
-        if time >= self.next {
-            let dt = time - self.last;
-            self.next = time + self.dt_min;
-            self.last = time;
-            Some(if dt > self.dt_max { self.dt_max } else { dt })
-        } else {
-            None
+        let elapsed = time - self.last;
+        self.last = time;
+        self.advance(elapsed)
+    }
+
+    pub fn advance(&mut self, elapsed: f64) -> u32 {
+        self.acc += elapsed;
+
+        let mut ticks = 0;
+        while self.acc >= self.step && ticks < self.max_steps {
+            self.acc -= self.step;
+            ticks += 1;
+        }
+
+        if self.acc >= self.step {
+            /* too far behind, drop the backlog instead of spiralling */
+            self.acc %= self.step;
         }
+
+        ticks
     }
+
+    /// How far between the last two ticks the wall clock is, 0..1.
+    pub fn alpha(&self) -> f32 {
+        (self.acc / self.step) as f32
+    }
+}
+
+#[test]
+fn test_timer() {
+    let mut t = Timer::new(10.0, 3);
+
+    assert_eq!(t.advance(0.05), 0);
+    assert!((t.alpha() - 0.5).abs() < 1e-6);
+
+    assert_eq!(t.advance(0.1), 1);
+    assert!((t.alpha() - 0.5).abs() < 1e-6);
+
+    assert_eq!(t.advance(1.0), 3);
+    assert!(t.alpha() < 1.0);
 }
@@ -0,0 +1,123 @@
+use cgmath::{ vec2, EuclideanVector };
+
+use physics::V32;
+
+/// Size and shape of the playing field.
+///
+/// The world is centered on the origin. Along an axis that wraps, things
+/// leaving one edge come back on the other, otherwise the edges are walls.
+/* sides of the smallest field that fits the panels of every player,
+ * and of the largest whose positions still fit in an i16 */
+pub const MIN_SIZE: f32 = 400.0;
+pub const MAX_SIZE: f32 = 60000.0;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct World {
+    pub width: f32,
+    pub height: f32,
+    pub wrap_x: bool,
+    pub wrap_y: bool,
+}
+
+fn min_image(d: f32, size: f32, wrap: bool) -> f32 {
+    if !wrap {
+        d
+    } else if d > size / 2.0 {
+        d - size
+    } else if d < -size / 2.0 {
+        d + size
+    } else {
+        d
+    }
+}
+
+impl World {
+    pub fn new(width: f32, height: f32) -> World {
+        World {
+            width: width,
+            height: height,
+            wrap_x: true,
+            wrap_y: true,
+        }
+    }
+
+    /// Whether both sides are between `MIN_SIZE` and `MAX_SIZE`.
+    pub fn is_valid(&self) -> bool {
+        let ok = |v: f32| v >= MIN_SIZE && v <= MAX_SIZE;
+        ok(self.width) && ok(self.height)
+    }
+
+    pub fn walled(self) -> World {
+        World {
+            wrap_x: false,
+            wrap_y: false,
+            ..self
+        }
+    }
+
+    pub fn lo(&self) -> V32 {
+        vec2(-self.width / 2.0, -self.height / 2.0)
+    }
+
+    pub fn hi(&self) -> V32 {
+        vec2(self.width / 2.0, self.height / 2.0)
+    }
+
+    /// Shortest vector from `b` to `a`, going across the edges if that is closer.
+    pub fn delta(&self, a: V32, b: V32) -> V32 {
+        let d = a - b;
+        vec2(min_image(d.x, self.width, self.wrap_x),
+             min_image(d.y, self.height, self.wrap_y))
+    }
+
+    pub fn distance(&self, a: V32, b: V32) -> f32 {
+        self.delta(a, b).length()
+    }
+
+    /// Places to draw something of radius `r` at `p`, including copies on the
+    /// other side of any wrapping edge it sticks out of.
+    pub fn images(&self, p: V32, r: f32) -> Vec<V32> {
+        let shifts = |v: f32, size: f32, wrap: bool| {
+            let mut s = vec![0.0];
+            if wrap && v - r < -size / 2.0 {
+                s.push(size);
+            }
+            if wrap && v + r > size / 2.0 {
+                s.push(-size);
+            }
+            s
+        };
+
+        let mut out = Vec::new();
+        for dy in shifts(p.y, self.height, self.wrap_y) {
+            for dx in shifts(p.x, self.width, self.wrap_x) {
+                out.push(p + vec2(dx, dy));
+            }
+        }
+        out
+    }
+}
+
+impl Default for World {
+    fn default() -> World {
+        World::new(700.0, 700.0)
+    }
+}
+
+#[test]
+fn test_world() {
+    let w = World::new(800.0, 400.0);
+    assert_eq!(w.delta(vec2(390.0, 0.0), vec2(-390.0, 0.0)), vec2(-20.0, 0.0));
+    assert_eq!(w.delta(vec2(0.0, 190.0), vec2(0.0, -190.0)), vec2(0.0, -20.0));
+    assert_eq!(w.images(vec2(395.0, 0.0), 10.0), vec![vec2(395.0, 0.0), vec2(-405.0, 0.0)]);
+
+    let w = w.walled();
+    assert_eq!(w.delta(vec2(390.0, 0.0), vec2(-390.0, 0.0)), vec2(780.0, 0.0));
+    assert_eq!(w.images(vec2(395.0, 195.0), 10.0).len(), 1);
+
+    assert!(w.is_valid() && World::default().is_valid());
+    assert!(!World::new(0.0, 500.0).is_valid());
+    assert!(!World::new(-800.0, 500.0).is_valid());
+    assert!(!World::new(::std::f32::NAN, 500.0).is_valid());
+    assert!(!World::new(800.0, 1e6).is_valid());
+}